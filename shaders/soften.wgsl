@@ -0,0 +1,15 @@
+// Averages each pixel with its four neighbours. `source`, `source_sampler`, `previous` and
+// `uniforms` come from the prelude every pass shares.
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let texel = uniforms.source_size.zw;
+
+	var color = textureSample(source, source_sampler, in.tex_coords).rgb * 4.0;
+	color += textureSample(source, source_sampler, in.tex_coords + vec2<f32>(texel.x, 0.0)).rgb;
+	color += textureSample(source, source_sampler, in.tex_coords - vec2<f32>(texel.x, 0.0)).rgb;
+	color += textureSample(source, source_sampler, in.tex_coords + vec2<f32>(0.0, texel.y)).rgb;
+	color += textureSample(source, source_sampler, in.tex_coords - vec2<f32>(0.0, texel.y)).rgb;
+
+	return vec4<f32>(color / 8.0, 1.0);
+}
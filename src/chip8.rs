@@ -1,12 +1,45 @@
-use std::fs;
+use std::{collections::BTreeSet, fs};
 
+use anyhow::{Context, Result, bail};
 use rand::Rng;
-use winit::keyboard::KeyCode;
 
-use crate::emulator::Emulator;
+use crate::{
+    debug::DebugTarget,
+    emulator::{AUDIO_SAMPLE_RATE, Emulator, FrameView, MachineClock, PixelFormat},
+    palette::Palette,
+};
+
+use self::{
+    debugger::{Fault, RamWrite},
+    disasm::{C8_RAM_END, Dialect, ROM_START},
+    trace::{TRACE_TARGET, TraceState, trace_line},
+};
+
+pub mod asm;
+pub mod debugger;
+pub mod disasm;
+pub mod trace;
 
 const C8_VBUF_WIDTH: usize = 64;
 const C8_VBUF_HEIGHT: usize = 32;
+const C8_TIMER_HZ: u64 = 60;
+const C8_DEFAULT_INSTRUCTIONS_PER_FRAME: u64 = 16;
+
+// The buzzer is a square wave while the sound timer is non-zero
+const C8_BEEP_HZ: f32 = 440.0;
+const C8_BEEP_VOLUME: f32 = 0.25;
+const C8_SAMPLES_PER_TIMER_TICK: usize = (AUDIO_SAMPLE_RATE as u64 / C8_TIMER_HZ) as usize;
+// Audio kept for `drain_audio`, beyond which the oldest is dropped. Debuggers run the core
+// without ever draining it.
+const C8_MAX_BUFFERED_SAMPLES: usize = AUDIO_SAMPLE_RATE as usize;
+
+// The keypad, named by the hex digit each key enters
+const C8_KEYS: [&str; 16] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F",
+];
+
+// Palette shade for each pixel value: off, on, and the two extra XO-CHIP plane combinations
+const C8_PIXEL_SHADES: [usize; 4] = [0, 3, 1, 2];
 
 pub struct Chip8 {
     v: [u8; 16],
@@ -16,34 +49,100 @@ pub struct Chip8 {
     pc: u16,
     delay_timer: u8,
     sound_timer: u8,
-    ram: [u8; 0xFFF],
+    ram: [u8; C8_RAM_END],
     keypad: [u8; 16],
     looking_for_key: bool,
     most_recent_key: u8,
     vbuf: [u8; C8_VBUF_WIDTH * C8_VBUF_HEIGHT],
-    off_color: [u8; 3],
-    on_color: [u8; 3], // rng: ThreadRng,
+    // vbuf in color, kept up to date as pixels change
+    framebuffer: [u8; C8_VBUF_WIDTH * C8_VBUF_HEIGHT * 4],
+    frame_ready: bool,
+    palette: [[u8; 3]; 4], // rng: ThreadRng,
+    instructions_per_frame: u64,
+    audio: Vec<f32>,
+    // Position within the beep's period, 0 to 1
+    beep_phase: f32,
+    // Addresses the debugger wants to hear about, and the first write to one of them
+    watchpoints: BTreeSet<u16>,
+    watch_hit: Option<RamWrite>,
+    // Why the core stopped, after which it stays put instead of running the instruction
+    fault: Option<Fault>,
 }
 
 impl Chip8 {
-    pub fn new(filename: &str) -> Self {
+    pub fn new(filename: &str) -> Result<Self> {
+        let bytes: Vec<u8> =
+            fs::read(filename).with_context(|| format!("Could not read {}", filename))?;
+
+        Chip8::from_rom(&bytes)
+    }
+
+    // Loads the ROM at ROM_START, failing if it runs past the end of RAM
+    pub fn from_rom(bytes: &[u8]) -> Result<Self> {
+        let max_size = Dialect::Chip8.max_rom_size();
+        if bytes.len() > max_size {
+            bail!(
+                "ROM is {} bytes, only {} fit in memory from {:03X}",
+                bytes.len(),
+                max_size,
+                ROM_START
+            );
+        }
+        let start = ROM_START as usize;
         let mut chip8 = Chip8::default();
+        chip8.ram[start..start + bytes.len()].copy_from_slice(bytes);
+
+        Ok(chip8)
+    }
+
+    // Speed of the CPU relative to the 60 Hz timers
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u64) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    // CHIP-8 keypad key 0x0 to 0xF
+    pub fn set_key(&mut self, key: u8, is_pressed: bool) {
+        let key = key & 0xF;
+        if is_pressed {
+            self.keypad[key as usize] = 1;
+            if self.looking_for_key && self.most_recent_key >= 16 {
+                self.most_recent_key = key;
+            }
+        } else {
+            self.keypad[key as usize] = 0;
+        }
+    }
 
-        let bytes: Vec<u8> = fs::read(filename).expect("Could not get file");
-        chip8.ram[0x200..(bytes.len() + 0x200)].copy_from_slice(&bytes[..]);
+    fn write_ram(&mut self, addr: usize, value: u8) {
+        if self.watchpoints.contains(&(addr as u16)) && self.watch_hit.is_none() {
+            self.watch_hit = Some(RamWrite {
+                pc: self.pc,
+                addr: addr as u16,
+                old: self.ram[addr],
+                new: value,
+            });
+        }
+        self.ram[addr] = value;
+    }
 
-        chip8
+    // Logged once, the debuggers also report it through `Stop::Fault`
+    fn set_fault(&mut self, fault: Fault) {
+        if self.fault.is_none() {
+            log::error!("CHIP-8 stopped: {}", fault);
+        }
+        self.fault = Some(fault);
     }
 
-    pub fn set_colors(&mut self, off_color: [u8; 3], on_color: [u8; 3]) {
-        self.off_color = off_color;
-        self.on_color = on_color;
+    fn update_pixel(&mut self, index: usize) {
+        let color = self.palette[C8_PIXEL_SHADES[self.vbuf[index] as usize & 0b11]];
+        self.framebuffer[index * 4..index * 4 + 3].copy_from_slice(&color);
+        self.framebuffer[index * 4 + 3] = 255;
     }
 }
 
 impl Default for Chip8 {
     fn default() -> Self {
-        let mut ram = [0u8; 0xFFF];
+        let mut ram = [0u8; C8_RAM_END];
         // 0
         ram[0] = 0xF0;
         ram[1] = 0x90;
@@ -141,6 +240,12 @@ impl Default for Chip8 {
         ram[78] = 0x80;
         ram[79] = 0x80;
 
+        // Opaque black, matching the default palette's off color
+        let mut framebuffer = [0u8; C8_VBUF_WIDTH * C8_VBUF_HEIGHT * 4];
+        for pixel in framebuffer.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+
         Self {
             v: Default::default(),
             sp: Default::default(),
@@ -154,176 +259,28 @@ impl Default for Chip8 {
             looking_for_key: false,
             most_recent_key: 16u8,
             vbuf: [0u8; C8_VBUF_WIDTH * C8_VBUF_HEIGHT],
-            off_color: [0u8, 0u8, 0u8],
-            on_color: [255u8, 255u8, 255u8],
+            framebuffer,
+            frame_ready: true,
+            // White on black
+            palette: [[0, 0, 0], [85, 85, 85], [170, 170, 170], [255, 255, 255]],
+            instructions_per_frame: C8_DEFAULT_INSTRUCTIONS_PER_FRAME,
+            audio: Vec::new(),
+            beep_phase: 0.0,
+            watchpoints: BTreeSet::new(),
+            watch_hit: None,
+            fault: None,
         }
     }
 }
 
 impl Emulator for Chip8 {
-    fn handle_key(&mut self, code: KeyCode, is_pressed: bool) {
-        match code {
-            KeyCode::KeyX => {
-                if is_pressed {
-                    self.keypad[0] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 0;
-                    }
-                } else {
-                    self.keypad[0] = 0;
-                }
-            }
-            KeyCode::Digit1 => {
-                if is_pressed {
-                    self.keypad[1] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 1;
-                    }
-                } else {
-                    self.keypad[1] = 0;
-                }
-            }
-            KeyCode::Digit2 => {
-                if is_pressed {
-                    self.keypad[2] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 2;
-                    }
-                } else {
-                    self.keypad[2] = 0;
-                }
-            }
-            KeyCode::Digit3 => {
-                if is_pressed {
-                    self.keypad[3] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 3;
-                    }
-                } else {
-                    self.keypad[3] = 0;
-                }
-            }
-            KeyCode::KeyQ => {
-                if is_pressed {
-                    self.keypad[4] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 4;
-                    }
-                } else {
-                    self.keypad[4] = 0;
-                }
-            }
-            KeyCode::KeyW => {
-                if is_pressed {
-                    self.keypad[5] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 5;
-                    }
-                } else {
-                    self.keypad[5] = 0;
-                }
-            }
-            KeyCode::KeyE => {
-                if is_pressed {
-                    self.keypad[6] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 6;
-                    }
-                } else {
-                    self.keypad[6] = 0;
-                }
-            }
-            KeyCode::KeyA => {
-                if is_pressed {
-                    self.keypad[7] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 7;
-                    }
-                } else {
-                    self.keypad[7] = 0;
-                }
-            }
-            KeyCode::KeyS => {
-                if is_pressed {
-                    self.keypad[8] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 8;
-                    }
-                } else {
-                    self.keypad[8] = 0;
-                }
-            }
-            KeyCode::KeyD => {
-                if is_pressed {
-                    self.keypad[9] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 9;
-                    }
-                } else {
-                    self.keypad[9] = 0;
-                }
-            }
-            KeyCode::KeyZ => {
-                if is_pressed {
-                    self.keypad[10] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 10;
-                    }
-                } else {
-                    self.keypad[10] = 0;
-                }
-            }
-            KeyCode::KeyC => {
-                if is_pressed {
-                    self.keypad[11] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 11;
-                    }
-                } else {
-                    self.keypad[11] = 0;
-                }
-            }
-            KeyCode::Digit4 => {
-                if is_pressed {
-                    self.keypad[12] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 12;
-                    }
-                } else {
-                    self.keypad[12] = 0;
-                }
-            }
-            KeyCode::KeyR => {
-                if is_pressed {
-                    self.keypad[13] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 13;
-                    }
-                } else {
-                    self.keypad[13] = 0;
-                }
-            }
-            KeyCode::KeyF => {
-                if is_pressed {
-                    self.keypad[14] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 14;
-                    }
-                } else {
-                    self.keypad[14] = 0;
-                }
-            }
-            KeyCode::KeyV => {
-                if is_pressed {
-                    self.keypad[15] = 1;
-                    if self.looking_for_key && self.most_recent_key >= 16 {
-                        self.most_recent_key = 15;
-                    }
-                } else {
-                    self.keypad[15] = 0;
-                }
-            }
-            _ => {}
+    fn buttons(&self) -> &'static [&'static str] {
+        &C8_KEYS
+    }
+
+    fn set_button(&mut self, button: usize, is_pressed: bool) {
+        if button < C8_KEYS.len() {
+            self.set_key(button as u8, is_pressed);
         }
     }
 
@@ -331,14 +288,40 @@ impl Emulator for Chip8 {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
+
+        for _ in 0..C8_SAMPLES_PER_TIMER_TICK {
+            let sample = if self.sound_timer == 0 {
+                0.0
+            } else if self.beep_phase < 0.5 {
+                C8_BEEP_VOLUME
+            } else {
+                -C8_BEEP_VOLUME
+            };
+            self.audio.push(sample);
+            self.beep_phase = (self.beep_phase + C8_BEEP_HZ / AUDIO_SAMPLE_RATE as f32).fract();
+        }
+        if self.audio.len() > C8_MAX_BUFFERED_SAMPLES {
+            let excess = self.audio.len() - C8_MAX_BUFFERED_SAMPLES;
+            self.audio.drain(..excess);
+        }
+
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
         }
     }
-    fn tick(&mut self) {
+
+    fn drain_audio(&mut self, samples: &mut Vec<f32>) {
+        samples.append(&mut self.audio);
+    }
+    fn tick(&mut self) -> u32 {
+        if self.pc as usize + 1 >= self.ram.len() {
+            self.set_fault(Fault::PcOutOfRange(self.pc));
+            return 1;
+        }
         let instruction: u16 =
             ((self.ram[self.pc as usize] as u16) << 8) | self.ram[self.pc as usize + 1] as u16;
-        println!("Instruction: {:X}", instruction);
+        let trace = log::log_enabled!(target: TRACE_TARGET, log::Level::Trace)
+            .then(|| TraceState::capture(self));
         let nnn = instruction & 0xFFF;
         let n = instruction & 0xF;
         let x = (instruction >> 8) & 0xF;
@@ -350,8 +333,13 @@ impl Emulator for Chip8 {
                 0x00E0 => {
                     // CLS
                     self.vbuf = [0u8; C8_VBUF_WIDTH * C8_VBUF_HEIGHT];
+                    for index in 0..self.vbuf.len() {
+                        self.update_pixel(index);
+                    }
+                    self.frame_ready = true;
                     self.pc += 2; // Move ahead
                 }
+                0x00EE if self.sp == 0 => self.set_fault(Fault::StackUnderflow(self.pc)),
                 0x00EE => {
                     // RET
                     self.pc = self.stack[self.sp as usize];
@@ -366,6 +354,9 @@ impl Emulator for Chip8 {
                 // JP addr
                 self.pc = nnn;
             }
+            2 if self.sp as usize + 1 >= self.stack.len() => {
+                self.set_fault(Fault::StackOverflow(self.pc));
+            }
             2 => {
                 // CALL addr
                 self.sp += 1;
@@ -453,8 +444,9 @@ impl Emulator for Chip8 {
                 }
                 6 => {
                     // SHR Vx {, Vy}
-                    self.v[0xf] = self.v[x as usize] & 0x1;
+                    let flag = self.v[x as usize] & 0x1;
                     self.v[x as usize] >>= 1;
+                    self.v[0xf] = flag;
 
                     self.pc += 2;
                 }
@@ -469,28 +461,23 @@ impl Emulator for Chip8 {
                 }
                 0xE => {
                     // SHL Vx {, Vy}
-                    self.v[0xf] = (self.v[x as usize].reverse_bits()) & 0x1;
+                    let flag = (self.v[x as usize].reverse_bits()) & 0x1;
                     self.v[x as usize] <<= 1;
+                    self.v[0xf] = flag;
 
                     self.pc += 2;
                 }
                 _ => {
                     // Unknown Instruction
-                    println!("Unknown instruction: {:X}", instruction);
+                    log::warn!("Unknown instruction {:04X} at {:04X}", instruction, self.pc);
                     self.pc += 2;
                 }
             },
-            9 => {
-                if n == 0 {
-                    // SNE Vx, Vy
-                    if self.v[x as usize] != self.v[y as usize] {
-                        self.pc += 4;
-                    } else {
-                        self.pc += 2;
-                    }
+            9 if n == 0 => {
+                // SNE Vx, Vy
+                if self.v[x as usize] != self.v[y as usize] {
+                    self.pc += 4;
                 } else {
-                    // Unknown Instruction
-                    println!("Unknown instruction: {:X}", instruction);
                     self.pc += 2;
                 }
             }
@@ -519,20 +506,21 @@ impl Emulator for Chip8 {
                     for dx in 0u8..8u8 {
                         let screen_x = self.v[x as usize].wrapping_add(dx) % C8_VBUF_WIDTH as u8;
                         let screen_y = self.v[y as usize].wrapping_add(dy) % C8_VBUF_HEIGHT as u8;
-                        let prev_vbuf_xy =
-                            self.vbuf[screen_x as usize + screen_y as usize * C8_VBUF_WIDTH];
-                        self.vbuf[screen_x as usize + screen_y as usize * C8_VBUF_WIDTH] ^=
-                            (sprite_byte >> dx) & 0x1;
-
-                        if self.vbuf[screen_x as usize + screen_y as usize * C8_VBUF_WIDTH] == 0
-                            && prev_vbuf_xy == 1
-                        {
+                        let index = screen_x as usize + screen_y as usize * C8_VBUF_WIDTH;
+                        let prev_vbuf_xy = self.vbuf[index];
+                        self.vbuf[index] ^= (sprite_byte >> dx) & 0x1;
+
+                        if self.vbuf[index] != prev_vbuf_xy {
+                            self.update_pixel(index);
+                        }
+                        if self.vbuf[index] == 0 && prev_vbuf_xy == 1 {
                             collision = true;
                         }
                     }
                 }
 
                 self.v[0xF] = if collision { 1 } else { 0 };
+                self.frame_ready = true;
 
                 self.pc += 2;
             }
@@ -555,7 +543,7 @@ impl Emulator for Chip8 {
                 }
                 _ => {
                     // Unkown Instruction
-                    println!("Unknown instruction: {:X}", instruction);
+                    log::warn!("Unknown instruction {:04X} at {:04X}", instruction, self.pc);
                     self.pc += 2;
                 }
             },
@@ -603,16 +591,16 @@ impl Emulator for Chip8 {
                     let hundreds = val / 100;
                     let tens = (val % 100) / 10;
                     let ones = val % 10;
-                    self.ram[self.i as usize] = hundreds;
-                    self.ram[self.i as usize + 1] = tens;
-                    self.ram[self.i as usize + 2] = ones;
+                    self.write_ram(self.i as usize, hundreds);
+                    self.write_ram(self.i as usize + 1, tens);
+                    self.write_ram(self.i as usize + 2, ones);
 
                     self.pc += 2;
                 }
                 0x55 => {
                     // LD [I], Vx
                     for reg in 0usize..x as usize + 1 {
-                        self.ram[self.i as usize + reg] = self.v[reg];
+                        self.write_ram(self.i as usize + reg, self.v[reg]);
                     }
                     self.pc += 2;
                     self.i += x + 1; // Quirk
@@ -627,33 +615,58 @@ impl Emulator for Chip8 {
                 }
                 _ => {
                     // Unkown instruction
-                    println!("Unknown instruction: {:X}", instruction);
+                    log::warn!("Unknown instruction {:04X} at {:04X}", instruction, self.pc);
                     self.pc += 2;
                 }
             },
 
             _ => {
                 // Unknown Instruction
-                println!("Unknown instruction: {:X}", instruction);
+                log::warn!("Unknown instruction {:04X} at {:04X}", instruction, self.pc);
                 self.pc += 2;
             }
         }
+
+        if let Some(before) = trace {
+            log::trace!(
+                target: TRACE_TARGET,
+                "{}",
+                trace_line(instruction, &before, &TraceState::capture(self))
+            );
+        }
+
+        1
     }
-    fn get_vbuf(&mut self) -> (Vec<u8>, usize, usize) {
-        (
-            self.vbuf
-                .to_vec()
-                .iter()
-                .flat_map(|val| {
-                    if *val == 0 {
-                        self.off_color
-                    } else {
-                        self.on_color
-                    }
-                })
-                .collect(),
-            C8_VBUF_WIDTH,
-            C8_VBUF_HEIGHT,
-        )
+    fn clock(&self) -> MachineClock {
+        // One cycle per instruction, with the timers running at 60 Hz
+        MachineClock {
+            hz: C8_TIMER_HZ * self.instructions_per_frame,
+            timer_period: self.instructions_per_frame,
+            frame_period: self.instructions_per_frame,
+        }
+    }
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.colors;
+
+        for index in 0..self.vbuf.len() {
+            self.update_pixel(index);
+        }
+        self.frame_ready = true;
+    }
+
+    fn framebuffer(&self) -> FrameView<'_> {
+        FrameView {
+            data: &self.framebuffer,
+            width: C8_VBUF_WIDTH,
+            height: C8_VBUF_HEIGHT,
+            format: PixelFormat::Rgba8,
+        }
+    }
+    fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    fn debug_target(&self) -> Option<&dyn DebugTarget> {
+        Some(self)
     }
 }
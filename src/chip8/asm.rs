@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow, bail};
+
+use super::disasm::{Dialect, ROM_START};
+
+// Assembles the syntax `disasm` prints back into a ROM, plus what is handy for writing programs
+// by hand:
+//
+// - `name:` labels, on their own line or before an instruction
+// - `NAME EQU expr` constants, which may refer to labels and other constants
+// - `DB` bytes and `DW` big-endian words, comma separated. DB also takes "strings".
+// - Numbers in decimal, hex (0x1F or $1F) or binary (0b1010), and expressions adding and
+//   subtracting them, e.g. `sprites + 5`
+// - `;` comments
+//
+// Mnemonics, registers and directives are case-insensitive; labels and constants are not.
+// Extension instructions need the matching dialect, e.g. `SCD 4` needs `Dialect::SuperChip`.
+
+// Constants defined in terms of each other are followed this deep before giving up
+const MAX_SYMBOL_DEPTH: usize = 32;
+
+const RESERVED: [&str; 10] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG", "EQU"];
+
+pub fn assemble(source: &str) -> Result<Vec<u8>> {
+    assemble_as(source, Dialect::Chip8)
+}
+
+pub fn assemble_as(source: &str, dialect: Dialect) -> Result<Vec<u8>> {
+    let mut assembler = Assembler::default();
+
+    // First pass: sizes don't depend on operand values, so every label's address is known
+    // before anything is encoded
+    let mut addr = ROM_START as usize;
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let Some(statement) = assembler
+            .parse_line(text, line, addr)
+            .map_err(|error| anyhow!("line {}: {:#}", line, error))?
+        else {
+            continue;
+        };
+        addr += statement.size();
+        assembler.statements.push(statement);
+    }
+
+    let ram_end = dialect.ram_end();
+    if addr > ram_end {
+        bail!(
+            "Program is {} bytes, only {} fit in memory",
+            addr - ROM_START as usize,
+            ram_end - ROM_START as usize
+        );
+    }
+
+    let mut rom = Vec::with_capacity(addr - ROM_START as usize);
+    for statement in &assembler.statements {
+        let bytes = assembler
+            .encode(statement, dialect)
+            .map_err(|error| anyhow!("line {}: {:#}", statement.line, error))?;
+        rom.extend(bytes);
+    }
+    Ok(rom)
+}
+
+enum Symbol {
+    Label(u16),
+    Constant { expr: String, line: usize },
+}
+
+struct Statement {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<String>,
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self.mnemonic.as_str() {
+            "DB" => self
+                .operands
+                .iter()
+                .map(|operand| string_literal(operand).map_or(1, |text| text.len()))
+                .sum(),
+            "DW" => self.operands.len() * 2,
+            "LD" if self
+                .operands
+                .get(1)
+                .is_some_and(|operand| long_operand(operand).is_some()) =>
+            {
+                4
+            }
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Assembler {
+    symbols: HashMap<String, Symbol>,
+    statements: Vec<Statement>,
+}
+
+impl Assembler {
+    // Returns the instruction or data on the line, if any, after recording its label or constant
+    fn parse_line(&mut self, text: &str, line: usize, addr: usize) -> Result<Option<Statement>> {
+        let mut text = strip_comment(text).trim();
+
+        if let Some((label, rest)) = text.split_once(':')
+            && is_identifier(label.trim())
+        {
+            self.define(label.trim(), Symbol::Label(addr as u16))?;
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            return Ok(None);
+        }
+
+        let (mnemonic, rest) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, rest)) => (mnemonic, rest.trim()),
+            None => (text, ""),
+        };
+
+        if let Some((keyword, expr)) = rest.split_once(char::is_whitespace)
+            && keyword.eq_ignore_ascii_case("EQU")
+        {
+            if !is_identifier(mnemonic) {
+                bail!("{} is not a valid constant name", mnemonic);
+            }
+            let expr = expr.trim().to_string();
+            self.define(mnemonic, Symbol::Constant { expr, line })?;
+            return Ok(None);
+        }
+
+        let operands = split_operands(rest)?;
+        Ok(Some(Statement {
+            line,
+            mnemonic: mnemonic.to_ascii_uppercase(),
+            operands,
+        }))
+    }
+
+    fn define(&mut self, name: &str, symbol: Symbol) -> Result<()> {
+        let upper = name.to_ascii_uppercase();
+        if RESERVED.contains(&upper.as_str()) || register(name).is_some() {
+            bail!("{} is a reserved name", name);
+        }
+        if self.symbols.insert(name.to_string(), symbol).is_some() {
+            bail!("{} is defined twice", name);
+        }
+        Ok(())
+    }
+
+    fn eval(&self, expr: &str, depth: usize) -> Result<i64> {
+        if depth > MAX_SYMBOL_DEPTH {
+            bail!("Constants refer to each other in a loop");
+        }
+
+        let mut total = 0i64;
+        let mut sign = 1;
+        let mut term = String::new();
+        let mut terms = Vec::new();
+        for c in expr.chars().chain(std::iter::once('+')) {
+            match c {
+                '+' | '-' if !term.trim().is_empty() => {
+                    terms.push((sign, std::mem::take(&mut term)));
+                    sign = if c == '-' { -1 } else { 1 };
+                }
+                // Unary signs
+                '+' => {}
+                '-' => sign = -sign,
+                _ => term.push(c),
+            }
+        }
+        if terms.is_empty() {
+            bail!("Expected a value");
+        }
+
+        for (sign, term) in terms {
+            let term = term.trim();
+            let value = match parse_number(term) {
+                Some(value) => value,
+                None => match self.symbols.get(term) {
+                    Some(Symbol::Label(addr)) => *addr as i64,
+                    Some(Symbol::Constant { expr, line }) => self
+                        .eval(expr, depth + 1)
+                        .map_err(|error| anyhow!("{} (line {}): {:#}", term, line, error))?,
+                    None if is_identifier(term) => bail!("Unknown label or constant {}", term),
+                    None => bail!("Invalid value {}", term),
+                },
+            };
+            total = value
+                .checked_mul(sign)
+                .and_then(|value| total.checked_add(value))
+                .ok_or_else(|| anyhow!("Value out of range"))?;
+        }
+        Ok(total)
+    }
+
+    fn value(&self, expr: &str, min: i64, max: i64, what: &str) -> Result<u16> {
+        let value = self.eval(expr, 0)?;
+        if value < min || value > max {
+            bail!("{} is {}, expected {} to {}", what, value, min, max);
+        }
+        Ok(value as u16)
+    }
+
+    fn addr(&self, expr: &str) -> Result<u16> {
+        self.value(expr, 0, 0xFFF, "Address")
+    }
+
+    // Negative bytes are stored in two's complement, so `ADD V0, -1` works
+    fn byte(&self, expr: &str) -> Result<u16> {
+        Ok(self.value(expr, -128, 0xFF, "Byte")? & 0xFF)
+    }
+
+    fn nibble(&self, expr: &str) -> Result<u16> {
+        self.value(expr, 0, 0xF, "Nibble")
+    }
+
+    fn encode(&self, statement: &Statement, dialect: Dialect) -> Result<Vec<u8>> {
+        match statement.mnemonic.as_str() {
+            "DB" => {
+                let mut bytes = Vec::new();
+                for operand in &statement.operands {
+                    match string_literal(operand) {
+                        Some(text) => bytes.extend(text.bytes()),
+                        None => bytes.push(self.byte(operand)? as u8),
+                    }
+                }
+                return Ok(bytes);
+            }
+            "DW" => {
+                let mut bytes = Vec::new();
+                for operand in &statement.operands {
+                    let word = self.value(operand, -32768, 0xFFFF, "Word")?;
+                    bytes.extend(word.to_be_bytes());
+                }
+                return Ok(bytes);
+            }
+            _ => {}
+        }
+
+        let operands: Vec<Operand> = statement.operands.iter().map(|o| operand(o)).collect();
+        if let Some(bytes) = self.instruction(&statement.mnemonic, &operands, dialect)? {
+            return Ok(bytes);
+        }
+
+        // Say which dialect would have accepted it
+        let text = format!("{} {}", statement.mnemonic, statement.operands.join(", "));
+        for extension in [Dialect::SuperChip, Dialect::XoChip] {
+            if extension > dialect
+                && self
+                    .instruction(&statement.mnemonic, &operands, extension)
+                    .is_ok_and(|bytes| bytes.is_some())
+            {
+                bail!("{} needs the {} dialect", text.trim(), extension);
+            }
+        }
+        bail!("Unknown instruction {}", text.trim())
+    }
+
+    // None if no instruction has this mnemonic and operands in `dialect`
+    fn instruction(
+        &self,
+        mnemonic: &str,
+        operands: &[Operand],
+        dialect: Dialect,
+    ) -> Result<Option<Vec<u8>>> {
+        use Operand::*;
+
+        let schip = dialect >= Dialect::SuperChip;
+        let xo = dialect >= Dialect::XoChip;
+        let xy = |x: u16, y: u16| x << 8 | y << 4;
+
+        let opcode = match (mnemonic, operands) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SYS", [Value(a)]) => self.addr(a)?,
+            ("JP", [Value(a)]) => 0x1000 | self.addr(a)?,
+            ("JP", [V(0), Value(a)]) => 0xB000 | self.addr(a)?,
+            ("CALL", [Value(a)]) => 0x2000 | self.addr(a)?,
+            ("SE", [V(x), Value(b)]) => 0x3000 | x << 8 | self.byte(b)?,
+            ("SNE", [V(x), Value(b)]) => 0x4000 | x << 8 | self.byte(b)?,
+            ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+            ("LD", [V(x), Value(b)]) => 0x6000 | x << 8 | self.byte(b)?,
+            ("ADD", [V(x), Value(b)]) => 0x7000 | x << 8 | self.byte(b)?,
+            ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+            ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+            ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+            ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+            ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+            ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+            ("SHR", [V(x)]) => 0x8006 | xy(*x, 0),
+            ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+            ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+            ("SHL", [V(x)]) => 0x800E | xy(*x, 0),
+            ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+            ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+            ("LD", [I, Value(a)]) => 0xA000 | self.addr(a)?,
+            ("RND", [V(x), Value(b)]) => 0xC000 | x << 8 | self.byte(b)?,
+            ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | xy(*x, *y) | self.nibble(n)?,
+            ("SKP", [V(x)]) => 0xE09E | x << 8,
+            ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+            ("LD", [V(x), DT]) => 0xF007 | x << 8,
+            ("LD", [V(x), K]) => 0xF00A | x << 8,
+            ("LD", [DT, V(x)]) => 0xF015 | x << 8,
+            ("LD", [ST, V(x)]) => 0xF018 | x << 8,
+            ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+            ("LD", [F, V(x)]) => 0xF029 | x << 8,
+            ("LD", [B, V(x)]) => 0xF033 | x << 8,
+            ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+            ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+
+            // SUPER-CHIP
+            ("SCD", [Value(n)]) if schip => 0x00C0 | self.nibble(n)?,
+            ("SCR", []) if schip => 0x00FB,
+            ("SCL", []) if schip => 0x00FC,
+            ("EXIT", []) if schip => 0x00FD,
+            ("LOW", []) if schip => 0x00FE,
+            ("HIGH", []) if schip => 0x00FF,
+            ("LD", [HF, V(x)]) if schip => 0xF030 | x << 8,
+            ("LD", [R, V(x)]) if schip => 0xF075 | x << 8,
+            ("LD", [V(x), R]) if schip => 0xF085 | x << 8,
+
+            // XO-CHIP
+            ("SCU", [Value(n)]) if xo => 0x00D0 | self.nibble(n)?,
+            ("SAVE", [V(x), V(y)]) if xo => 0x5002 | xy(*x, *y),
+            ("LOAD", [V(x), V(y)]) if xo => 0x5003 | xy(*x, *y),
+            ("PLANE", [Value(n)]) if xo => 0xF001 | self.nibble(n)? << 8,
+            ("AUDIO", []) if xo => 0xF002,
+            ("PITCH", [V(x)]) if xo => 0xF03A | x << 8,
+            ("LD", [I, Long(a)]) if xo => {
+                let addr = self.value(a, 0, 0xFFFF, "Address")?;
+                let mut bytes = 0xF000u16.to_be_bytes().to_vec();
+                bytes.extend(addr.to_be_bytes());
+                return Ok(Some(bytes));
+            }
+
+            _ => return Ok(None),
+        };
+        Ok(Some(opcode.to_be_bytes().to_vec()))
+    }
+}
+
+enum Operand<'a> {
+    V(u16),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    HF,
+    B,
+    R,
+    // XO-CHIP's 16-bit address for `LD I`
+    Long(&'a str),
+    Value(&'a str),
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    if let Some(x) = register(text) {
+        return Operand::V(x);
+    }
+    if let Some(expr) = long_operand(text) {
+        return Operand::Long(expr);
+    }
+    match text.to_ascii_uppercase().as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DT,
+        "ST" => Operand::ST,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::HF,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ => Operand::Value(text),
+    }
+}
+
+// V0 to VF
+fn register(text: &str) -> Option<u16> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u16::from_str_radix(digit, 16).ok()
+}
+
+fn long_operand(text: &str) -> Option<&str> {
+    let (keyword, expr) = text.split_once(char::is_whitespace)?;
+    keyword.eq_ignore_ascii_case("long").then_some(expr.trim())
+}
+
+fn string_literal(text: &str) -> Option<&str> {
+    text.strip_prefix('"')?.strip_suffix('"')
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+// Everything from a `;` outside of a string
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn split_operands(text: &str) -> Result<Vec<String>> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            ',' if !in_string => operands.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if in_string {
+        bail!("Unterminated string");
+    }
+    operands.push(current);
+
+    operands
+        .into_iter()
+        .map(|operand| {
+            let operand = operand.trim();
+            if operand.is_empty() {
+                bail!("Empty operand");
+            }
+            Ok(operand.to_string())
+        })
+        .collect()
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text = text.replace('_', "");
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('$')) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
@@ -0,0 +1,521 @@
+use std::{
+    collections::BTreeSet,
+    fmt,
+    io::{BufRead, Write},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use image::RgbaImage;
+
+use super::{Chip8, disasm::disassemble};
+use crate::{
+    debug::{DebugTarget, Register},
+    emulator::Emulator,
+    headless::frame_ascii,
+    scheduler::Scheduler,
+};
+
+// `continue` and `until` give up after this many instructions, a few minutes of emulated time
+pub const DEFAULT_RUN_LIMIT: u64 = 10_000_000;
+
+// A write to a watched RAM address, by the instruction at `pc`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RamWrite {
+    pub pc: u16,
+    pub addr: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+// Something a ROM did that the core can't carry on from, by the instruction at the address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    // Too close to the end of RAM to fetch a whole instruction
+    PcOutOfRange(u16),
+    // RET with nothing to return to
+    StackUnderflow(u16),
+    // CALL with all 15 return addresses in use
+    StackOverflow(u16),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::PcOutOfRange(pc) => write!(f, "PC {:04X} is past the end of RAM", pc),
+            Fault::StackUnderflow(pc) => write!(f, "RET at {:04X} with an empty stack", pc),
+            Fault::StackOverflow(pc) => write!(f, "CALL at {:04X} with a full stack", pc),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    // Return addresses, innermost call last
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    // Blocked in LD Vx, K
+    pub waiting_for_key: bool,
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (row, registers) in self.v.chunks(8).enumerate() {
+            for (column, value) in registers.iter().enumerate() {
+                if column > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "V{:X}={:02X}", row * 8 + column, value)?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "I={:04X} PC={:04X} DT={:02X} ST={:02X}",
+            self.i, self.pc, self.delay_timer, self.sound_timer
+        )?;
+        if self.waiting_for_key {
+            write!(f, " (waiting for a key)")?;
+        }
+        write!(f, "\nStack:")?;
+        for addr in &self.stack {
+            write!(f, " {:04X}", addr)?;
+        }
+        Ok(())
+    }
+}
+
+// Inspection used by the debugger, which can also be used on its own
+impl Chip8 {
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            stack: self.stack[1..=self.sp as usize].to_vec(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            waiting_for_key: self.looking_for_key,
+        }
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    // The 64x32 display row by row, 0 for off and 1 for on (2 and 3 for XO-CHIP's second
+    // plane), whatever the palette
+    pub fn vbuf(&self) -> &[u8] {
+        &self.vbuf
+    }
+
+    // The big-endian opcode at `addr`, 0 past the end of RAM
+    pub fn opcode_at(&self, addr: u16) -> u16 {
+        let byte = |addr: u16| self.ram.get(addr as usize).copied().unwrap_or(0) as u16;
+        byte(addr) << 8 | byte(addr.wrapping_add(1))
+    }
+}
+
+const V_NAMES: [&str; 16] = [
+    "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "va", "vb", "vc", "vd", "ve", "vf",
+];
+
+// V0-VF, then I, PC, SP, DT and ST
+impl DebugTarget for Chip8 {
+    fn register_file(&self) -> Vec<Register> {
+        let byte = |name, value: u8| Register {
+            name,
+            bits: 8,
+            value: value as u16,
+        };
+        let word = |name, value| Register {
+            name,
+            bits: 16,
+            value,
+        };
+        let mut registers: Vec<Register> = V_NAMES
+            .iter()
+            .zip(self.v)
+            .map(|(name, value)| byte(name, value))
+            .collect();
+        registers.extend([
+            word("i", self.i),
+            word("pc", self.pc),
+            byte("sp", self.sp),
+            byte("dt", self.delay_timer),
+            byte("st", self.sound_timer),
+        ]);
+        registers
+    }
+
+    fn set_register(&mut self, index: usize, value: u16) -> bool {
+        match index {
+            0..16 => self.v[index] = value as u8,
+            16 => self.i = value,
+            // Too close to the end of RAM to fetch from
+            17 if (value as usize) + 1 < self.ram.len() => self.pc = value,
+            // Past the end of the stack would crash the next CALL or RET
+            18 if (value as usize) < self.stack.len() => self.sp = value as u8,
+            19 => self.delay_timer = value as u8,
+            20 => self.sound_timer = value as u8,
+            _ => return false,
+        }
+        true
+    }
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn disassemble(&self, addr: u16) -> (String, u16) {
+        (disassemble(self.opcode_at(addr)), 2)
+    }
+
+    // The stack, innermost call last
+    fn status(&self) -> Vec<String> {
+        let mut stack = String::from("Stack:");
+        for addr in &self.stack[1..=self.sp as usize] {
+            stack.push_str(&format!(" {:03X}", addr));
+        }
+        let mut status = vec![stack];
+        if self.looking_for_key {
+            status.push("Waiting for a key".to_string());
+        }
+        status
+    }
+
+    fn read_memory(&self, addr: u16) -> Option<u8> {
+        self.ram.get(addr as usize).copied()
+    }
+
+    fn write_memory(&mut self, addr: u16, value: u8) -> bool {
+        match self.ram.get_mut(addr as usize) {
+            Some(byte) => {
+                *byte = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn big_endian(&self) -> bool {
+        true
+    }
+}
+
+// Why execution stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(u16),
+    Watchpoint(RamWrite),
+    Reached(u16),
+    // The core can't go on until the registers are changed, see `Fault`
+    Fault(Fault),
+    // Ran for the whole instruction limit
+    Limit,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Stepped => Ok(()),
+            Stop::Breakpoint(addr) => write!(f, "Breakpoint at {:04X}", addr),
+            Stop::Watchpoint(write) => write!(
+                f,
+                "Watchpoint: {:04X} changed from {:02X} to {:02X} at PC {:04X}",
+                write.addr, write.old, write.new, write.pc
+            ),
+            Stop::Reached(addr) => write!(f, "Reached {:04X}", addr),
+            Stop::Fault(fault) => write!(f, "Fault: {}", fault),
+            Stop::Limit => write!(f, "Stopped after the instruction limit"),
+        }
+    }
+}
+
+// Runs a Chip8 one instruction at a time on the frontend's scheduler, so the timers keep the
+// same pace relative to the CPU as in the window
+pub struct Debugger {
+    chip8: Chip8,
+    scheduler: Scheduler,
+    breakpoints: BTreeSet<u16>,
+    instructions: u64,
+}
+
+impl Debugger {
+    pub fn new(chip8: Chip8) -> Self {
+        Self {
+            scheduler: Scheduler::new(chip8.clock()),
+            chip8,
+            breakpoints: BTreeSet::new(),
+            instructions: 0,
+        }
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    // Instructions run since the debugger was created
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    // Returns false if there already was one at `addr`
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    // Stops execution after any instruction that writes to `addr`, even with the same value
+    pub fn add_watchpoint(&mut self, addr: u16) -> bool {
+        self.chip8.watchpoints.insert(addr)
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: u16) -> bool {
+        self.chip8.watchpoints.remove(&addr)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.chip8.watchpoints.iter().copied()
+    }
+
+    pub fn step(&mut self) -> Stop {
+        self.scheduler.step(&mut self.chip8);
+        self.instructions += 1;
+
+        if let Some(fault) = self.chip8.fault.take() {
+            return Stop::Fault(fault);
+        }
+        match self.chip8.watch_hit.take() {
+            Some(write) => Stop::Watchpoint(write),
+            None => Stop::Stepped,
+        }
+    }
+
+    // Runs until a breakpoint, a watchpoint, a fault, PC reaching `target` or `limit` instructions.
+    // The instruction at the current PC always runs, so this can resume from a breakpoint.
+    pub fn run(&mut self, target: Option<u16>, limit: u64) -> Stop {
+        for count in 0..limit {
+            let pc = self.chip8.pc;
+            if count > 0 {
+                if target == Some(pc) {
+                    return Stop::Reached(pc);
+                }
+                if self.breakpoints.contains(&pc) {
+                    return Stop::Breakpoint(pc);
+                }
+            }
+
+            match self.step() {
+                Stop::Stepped => {}
+                stop => return stop,
+            }
+        }
+        Stop::Limit
+    }
+
+    pub fn screen(&self) -> RgbaImage {
+        let frame = self.chip8.framebuffer();
+        RgbaImage::from_raw(frame.width as u32, frame.height as u32, frame.data.to_vec())
+            .expect("Framebuffer is smaller than its declared size")
+    }
+}
+
+const HELP: &str = "\
+step [n]          run n instructions (1 by default), also an empty line
+continue          run until a breakpoint or watchpoint
+until <addr>      run until PC reaches addr
+break <addr>      stop before the instruction at addr runs
+delete <addr>     remove a breakpoint
+watch <addr>      stop after a write to addr
+unwatch <addr>    remove a watchpoint
+list              show breakpoints and watchpoints
+regs              show registers, stack and timers
+mem <addr> [len]  hex dump of RAM (64 bytes by default)
+key <0-F> [up]    press or release a keypad key
+screen            print the display
+quit
+Addresses and keys are hexadecimal, optionally prefixed with 0x or $.";
+
+// Reads commands from `input` until it ends or `quit`. Command errors are printed and the
+// session continues; only I/O errors end it early.
+pub fn repl(debugger: &mut Debugger, input: impl BufRead, mut output: impl Write) -> Result<()> {
+    writeln!(output, "Type help for a list of commands")?;
+    print_position(debugger, &mut output)?;
+
+    let mut lines = input.lines();
+    loop {
+        write!(output, "(chip8) ")?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            writeln!(output)?;
+            return Ok(());
+        };
+        let line = line?;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => ("step", &[][..]),
+        };
+        if matches!(command, "q" | "quit") {
+            return Ok(());
+        }
+
+        if let Err(error) = run_command(debugger, command, args, &mut output) {
+            writeln!(output, "{:#}", error)?;
+        }
+    }
+}
+
+fn run_command(
+    debugger: &mut Debugger,
+    command: &str,
+    args: &[&str],
+    output: &mut impl Write,
+) -> Result<()> {
+    let arg = |index: usize| {
+        args.get(index)
+            .copied()
+            .ok_or_else(|| anyhow!("{} needs an address", command))
+    };
+
+    match command {
+        "s" | "step" => {
+            let count = match args.first() {
+                Some(count) => count
+                    .parse::<u64>()
+                    .with_context(|| format!("Invalid count {}", count))?,
+                None => 1,
+            };
+            let mut stop = Stop::Stepped;
+            for _ in 0..count {
+                stop = debugger.step();
+                if stop != Stop::Stepped {
+                    break;
+                }
+            }
+            report_stop(debugger, stop, output)?;
+        }
+        "c" | "continue" => {
+            let stop = debugger.run(None, DEFAULT_RUN_LIMIT);
+            report_stop(debugger, stop, output)?;
+        }
+        "u" | "until" => {
+            let target = parse_hex(arg(0)?)?;
+            let stop = debugger.run(Some(target), DEFAULT_RUN_LIMIT);
+            report_stop(debugger, stop, output)?;
+        }
+        "b" | "break" => {
+            let addr = parse_hex(arg(0)?)?;
+            debugger.add_breakpoint(addr);
+            writeln!(output, "Breakpoint at {:04X}", addr)?;
+        }
+        "d" | "delete" => {
+            let addr = parse_hex(arg(0)?)?;
+            if !debugger.remove_breakpoint(addr) {
+                bail!("No breakpoint at {:04X}", addr);
+            }
+        }
+        "w" | "watch" => {
+            let addr = parse_hex(arg(0)?)?;
+            debugger.add_watchpoint(addr);
+            writeln!(output, "Watching {:04X}", addr)?;
+        }
+        "unwatch" => {
+            let addr = parse_hex(arg(0)?)?;
+            if !debugger.remove_watchpoint(addr) {
+                bail!("No watchpoint at {:04X}", addr);
+            }
+        }
+        "l" | "list" => {
+            for addr in debugger.breakpoints() {
+                writeln!(output, "Breakpoint at {:04X}", addr)?;
+            }
+            for addr in debugger.watchpoints() {
+                writeln!(output, "Watching {:04X}", addr)?;
+            }
+        }
+        "r" | "regs" => writeln!(output, "{}", debugger.chip8().registers())?,
+        "m" | "mem" => {
+            let start = parse_hex(arg(0)?)? as usize;
+            let len = match args.get(1) {
+                Some(len) => parse_hex(len)? as usize,
+                None => 64,
+            };
+            let ram = debugger.chip8().ram();
+            let end = (start + len).min(ram.len());
+            for (row, bytes) in ram
+                .get(start..end)
+                .unwrap_or_default()
+                .chunks(16)
+                .enumerate()
+            {
+                write!(output, "{:04X}:", start + row * 16)?;
+                for byte in bytes {
+                    write!(output, " {:02X}", byte)?;
+                }
+                writeln!(output)?;
+            }
+        }
+        "k" | "key" => {
+            let key = parse_hex(arg(0)?)?;
+            if key > 0xF {
+                bail!("Keys go from 0 to F");
+            }
+            let is_pressed = match args.get(1) {
+                None | Some(&"down") => true,
+                Some(&"up") => false,
+                Some(other) => bail!("Expected up or down, got {}", other),
+            };
+            debugger.chip8_mut().set_key(key as u8, is_pressed);
+        }
+        "screen" => write!(output, "{}", frame_ascii(&debugger.screen()))?,
+        "h" | "help" => writeln!(output, "{}", HELP)?,
+        _ => bail!("Unknown command {}, type help for a list", command),
+    }
+    Ok(())
+}
+
+fn report_stop(debugger: &Debugger, stop: Stop, output: &mut impl Write) -> Result<()> {
+    if stop != Stop::Stepped {
+        writeln!(output, "{}", stop)?;
+    }
+    print_position(debugger, output)
+}
+
+fn print_position(debugger: &Debugger, output: &mut impl Write) -> Result<()> {
+    let pc = debugger.chip8().registers().pc;
+    let opcode = debugger.chip8().opcode_at(pc);
+    writeln!(
+        output,
+        "{:04X}: {:04X}  {}",
+        pc,
+        opcode,
+        disassemble(opcode)
+    )?;
+    Ok(())
+}
+
+pub fn parse_hex(text: &str) -> Result<u16> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix('$'))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).with_context(|| format!("Invalid hex number {}", text))
+}
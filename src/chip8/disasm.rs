@@ -0,0 +1,276 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+
+// Where `Chip8` loads ROMs
+pub const ROM_START: u16 = 0x200;
+
+// Ends of RAM in `Chip8` and in XO-CHIP
+pub const C8_RAM_END: usize = 0xFFF;
+pub const XO_RAM_END: usize = 0x10000;
+
+// XO-CHIP's `LD I, long 0xNNNN`, followed by the address in the next word
+const XO_LONG_I: u16 = 0xF000;
+
+// Instruction sets, each a superset of the one before. The core only runs plain CHIP-8.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Dialect {
+    #[default]
+    Chip8,
+    #[value(name = "schip")]
+    SuperChip,
+    XoChip,
+}
+
+impl Dialect {
+    pub fn ram_end(self) -> usize {
+        if self >= Dialect::XoChip {
+            XO_RAM_END
+        } else {
+            C8_RAM_END
+        }
+    }
+
+    // Largest ROM that fits in RAM from ROM_START
+    pub fn max_rom_size(self) -> usize {
+        self.ram_end() - ROM_START as usize
+    }
+}
+
+// The names used on the command line
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+// Mnemonics follow Cowgod's technical reference, the same names as the comments in
+// `Chip8::tick`. Addresses are printed as 0xNNN, bytes as 0xKK and sprite heights in decimal.
+// Opcodes that are not instructions come out as `DW 0xNNNN` data words.
+pub fn disassemble(opcode: u16) -> String {
+    disassemble_as(opcode, Dialect::Chip8)
+}
+
+pub fn disassemble_as(opcode: u16, dialect: Dialect) -> String {
+    mnemonic(opcode, dialect, &|addr| format!("0x{:03X}", addr))
+}
+
+fn mnemonic(opcode: u16, dialect: Dialect, address: &dyn Fn(u16) -> String) -> String {
+    let nnn = opcode & 0xFFF;
+    let n = opcode & 0xF;
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+    let kk = opcode & 0xFF;
+    let schip = dialect >= Dialect::SuperChip;
+    let xo = dialect >= Dialect::XoChip;
+
+    match (opcode >> 12, n) {
+        _ if opcode == 0x00E0 => "CLS".to_string(),
+        _ if opcode == 0x00EE => "RET".to_string(),
+        // SUPER-CHIP screen control
+        _ if schip && opcode & 0xFFF0 == 0x00C0 => format!("SCD {}", n),
+        _ if schip && opcode == 0x00FB => "SCR".to_string(),
+        _ if schip && opcode == 0x00FC => "SCL".to_string(),
+        _ if schip && opcode == 0x00FD => "EXIT".to_string(),
+        _ if schip && opcode == 0x00FE => "LOW".to_string(),
+        _ if schip && opcode == 0x00FF => "HIGH".to_string(),
+        _ if xo && opcode & 0xFFF0 == 0x00D0 => format!("SCU {}", n),
+        _ if xo && opcode == 0xF002 => "AUDIO".to_string(),
+        (0x5, 0x2) if xo => format!("SAVE V{:X}, V{:X}", x, y),
+        (0x5, 0x3) if xo => format!("LOAD V{:X}, V{:X}", x, y),
+        (0x0, _) => format!("SYS {}", address(nnn)),
+        (0x1, _) => format!("JP {}", address(nnn)),
+        (0x2, _) => format!("CALL {}", address(nnn)),
+        (0x3, _) => format!("SE V{:X}, 0x{:02X}", x, kk),
+        (0x4, _) => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        (0x5, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x6, _) => format!("LD V{:X}, 0x{:02X}", x, kk),
+        (0x7, _) => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        (0x8, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _) => format!("LD I, {}", address(nnn)),
+        (0xB, _) => format!("JP V0, {}", address(nnn)),
+        (0xC, _) => format!("RND V{:X}, 0x{:02X}", x, kk),
+        (0xD, _) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE, _) if kk == 0x9E => format!("SKP V{:X}", x),
+        (0xE, _) if kk == 0xA1 => format!("SKNP V{:X}", x),
+        (0xF, _) => match kk {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            0x30 if schip => format!("LD HF, V{:X}", x),
+            0x75 if schip => format!("LD R, V{:X}", x),
+            0x85 if schip => format!("LD V{:X}, R", x),
+            0x01 if xo => format!("PLANE {}", x),
+            0x3A if xo => format!("PITCH V{:X}", x),
+            _ => format!("DW 0x{:04X}", opcode),
+        },
+        _ => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+// What an address operand points at, in order of precedence when a location is used as several
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TargetKind {
+    Subroutine,
+    Code,
+    Data,
+}
+
+impl TargetKind {
+    fn label(self, addr: u16) -> String {
+        let prefix = match self {
+            TargetKind::Subroutine => "sub",
+            TargetKind::Code => "loc",
+            TargetKind::Data => "data",
+        };
+        format!("{}_{:03X}", prefix, addr)
+    }
+}
+
+// The address `opcode` jumps to, calls or points I at. XO-CHIP's long `LD I` is handled by
+// `disassemble_rom_as` as it spans two words.
+pub fn target(opcode: u16) -> Option<(u16, TargetKind)> {
+    let nnn = opcode & 0xFFF;
+    match opcode >> 12 {
+        0x1 => Some((nnn, TargetKind::Code)),
+        0x2 => Some((nnn, TargetKind::Subroutine)),
+        0xA => Some((nnn, TargetKind::Data)),
+        // Jump tables
+        0xB => Some((nnn, TargetKind::Code)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    // Two bytes, four for XO-CHIP's long `LD I`, or one for the last byte of an odd-sized ROM
+    pub bytes: Vec<u8>,
+    pub label: Option<String>,
+    pub text: String,
+}
+
+// A whole ROM, one line per 16-bit word from `ROM_START`. Words are decoded in a linear sweep,
+// so sprites and other data come out as whatever instruction they happen to look like.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Listing {
+    pub lines: Vec<Line>,
+}
+
+impl fmt::Display for Listing {
+    // Labels on their own lines and instructions indented, with the address and raw bytes in a
+    // trailing comment, so the listing can be assembled back into the same ROM
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = &line.label {
+                writeln!(f, "{}:", label)?;
+            }
+            let bytes: Vec<String> = line.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            writeln!(
+                f,
+                "    {:<24}; {:03X}: {}",
+                line.text,
+                line.addr,
+                bytes.join("")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Targets inside the ROM that fall on a line get a label, e.g. `sub_208`, used by the operands
+// that refer to them. ROMs too large to address are rejected.
+pub fn disassemble_rom(rom: &[u8]) -> Result<Listing> {
+    disassemble_rom_as(rom, Dialect::Chip8)
+}
+
+pub fn disassemble_rom_as(rom: &[u8], dialect: Dialect) -> Result<Listing> {
+    if rom.len() > dialect.max_rom_size() {
+        bail!(
+            "ROM is {} bytes, only {} fit in memory from {:03X}",
+            rom.len(),
+            dialect.max_rom_size(),
+            ROM_START
+        );
+    }
+
+    // Split into lines first so labels are only placed where a line starts
+    let mut words: Vec<(u16, &[u8])> = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let mut len = 2.min(rom.len() - offset);
+        if dialect >= Dialect::XoChip
+            && rom.len() - offset >= 4
+            && rom[offset..offset + 2] == XO_LONG_I.to_be_bytes()
+        {
+            len = 4;
+        }
+        words.push((ROM_START + offset as u16, &rom[offset..offset + len]));
+        offset += len;
+    }
+
+    let mut targets: Vec<(u16, TargetKind)> = Vec::new();
+    for (_, bytes) in &words {
+        match **bytes {
+            [high, low] => targets.extend(target(u16::from_be_bytes([high, low]))),
+            [_, _, high, low] => targets.push((u16::from_be_bytes([high, low]), TargetKind::Data)),
+            _ => {}
+        }
+    }
+    let starts: BTreeSet<u16> = words.iter().map(|(addr, _)| *addr).collect();
+    let mut labels: BTreeMap<u16, TargetKind> = BTreeMap::new();
+    for (addr, kind) in targets {
+        if starts.contains(&addr) {
+            let entry = labels.entry(addr).or_insert(kind);
+            *entry = (*entry).min(kind);
+        }
+    }
+    let address = |addr: u16| match labels.get(&addr) {
+        Some(kind) => kind.label(addr),
+        None => format!("0x{:03X}", addr),
+    };
+
+    let lines = words
+        .iter()
+        .map(|(addr, bytes)| Line {
+            addr: *addr,
+            bytes: bytes.to_vec(),
+            label: labels.get(addr).map(|kind| kind.label(*addr)),
+            text: match **bytes {
+                [high, low] => mnemonic(u16::from_be_bytes([high, low]), dialect, &address),
+                [_, _, high, low] => {
+                    let target = u16::from_be_bytes([high, low]);
+                    match labels.get(&target) {
+                        Some(kind) => format!("LD I, long {}", kind.label(target)),
+                        None => format!("LD I, long 0x{:04X}", target),
+                    }
+                }
+                _ => format!("DB 0x{:02X}", bytes[0]),
+            },
+        })
+        .collect();
+
+    Ok(Listing { lines })
+}
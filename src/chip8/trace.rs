@@ -0,0 +1,73 @@
+use std::fmt::Write;
+
+use super::{Chip8, disasm::disassemble};
+
+// Log target of the execution trace, enabled at trace level with `--trace` or
+// `RUST_LOG=emulators::chip8::trace=trace`
+pub const TRACE_TARGET: &str = "emulators::chip8::trace";
+
+// The machine state a trace line is made of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceState {
+    pub pc: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl TraceState {
+    pub fn capture(chip8: &Chip8) -> Self {
+        Self {
+            pc: chip8.pc,
+            v: chip8.v,
+            i: chip8.i,
+            sp: chip8.sp,
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+        }
+    }
+}
+
+// One line per instruction: the state before it runs, in fixed-width fields that can be diffed
+// against other emulators' logs after cutting at the first ';', then its disassembly and the
+// registers it changed, if any.
+//
+// PC:0200 OP:6005 I:0000 SP:0 DT:00 ST:00 V:00,00,..,00 ; LD V0, 0x05 ; V0=05
+pub fn trace_line(opcode: u16, before: &TraceState, after: &TraceState) -> String {
+    let mut line = format!(
+        "PC:{:04X} OP:{:04X} I:{:04X} SP:{:X} DT:{:02X} ST:{:02X} V:",
+        before.pc, opcode, before.i, before.sp, before.delay_timer, before.sound_timer
+    );
+    for (index, value) in before.v.iter().enumerate() {
+        if index > 0 {
+            line.push(',');
+        }
+        let _ = write!(line, "{:02X}", value);
+    }
+    let _ = write!(line, " ; {}", disassemble(opcode));
+
+    let mut changes = String::new();
+    for (index, (old, new)) in before.v.iter().zip(after.v).enumerate() {
+        if *old != new {
+            let _ = write!(changes, " V{:X}={:02X}", index, new);
+        }
+    }
+    if before.i != after.i {
+        let _ = write!(changes, " I={:04X}", after.i);
+    }
+    if before.sp != after.sp {
+        let _ = write!(changes, " SP={:X}", after.sp);
+    }
+    if before.delay_timer != after.delay_timer {
+        let _ = write!(changes, " DT={:02X}", after.delay_timer);
+    }
+    if before.sound_timer != after.sound_timer {
+        let _ = write!(changes, " ST={:02X}", after.sound_timer);
+    }
+    if !changes.is_empty() {
+        let _ = write!(line, " ;{}", changes);
+    }
+    line
+}
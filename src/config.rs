@@ -0,0 +1,402 @@
+use std::fmt;
+
+use anyhow::{Result, anyhow, bail};
+
+// Reader for the small subset of TOML used by the frontend's config files: `key = value` pairs,
+// `[table]` and `[[array.of.tables]]` headers with bare or quoted keys, strings, numbers,
+// booleans and (possibly multi-line) arrays. Inline tables and dates are not supported.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(text) => write!(f, "{:?}", text),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    pub entries: Vec<Entry>,
+}
+
+impl Table {
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.key == key)
+    }
+
+    pub fn str(&self, key: &str) -> Result<Option<&str>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::String(text),
+                ..
+            }) => Ok(Some(text)),
+            Some(entry) => Err(type_error(entry, "a string")),
+        }
+    }
+
+    pub fn f64(&self, key: &str) -> Result<Option<f64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::Number(number),
+                ..
+            }) => Ok(Some(*number)),
+            Some(entry) => Err(type_error(entry, "a number")),
+        }
+    }
+
+    pub fn bool(&self, key: &str) -> Result<Option<bool>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::Bool(value),
+                ..
+            }) => Ok(Some(*value)),
+            Some(entry) => Err(type_error(entry, "true or false")),
+        }
+    }
+
+    // A single string is accepted as a one element list
+    pub fn str_list(&self, key: &str) -> Result<Option<Vec<&str>>> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+
+        match &entry.value {
+            Value::String(text) => Ok(Some(vec![text])),
+            Value::Array(values) => values
+                .iter()
+                .map(|value| match value {
+                    Value::String(text) => Ok(text.as_str()),
+                    _ => Err(type_error(entry, "a list of strings")),
+                })
+                .collect::<Result<_>>()
+                .map(Some),
+            _ => Err(type_error(entry, "a list of strings")),
+        }
+    }
+}
+
+fn type_error(entry: &Entry, expected: &str) -> anyhow::Error {
+    anyhow!(
+        "line {}: expected {} to be {}, got {}",
+        entry.line,
+        entry.key,
+        expected,
+        entry.value
+    )
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    // `[chip8.rom."PONG.ch8"]` is ["chip8", "rom", "PONG.ch8"]
+    pub path: Vec<String>,
+    // Declared with [[double brackets]]
+    pub array: bool,
+    pub line: usize,
+    pub table: Table,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    // Keys before the first header
+    pub root: Table,
+    pub sections: Vec<Section>,
+}
+
+impl Document {
+    pub fn section(&self, path: &[&str]) -> Option<&Section> {
+        self.sections.iter().find(|section| section.path == path)
+    }
+
+    pub fn sections<'a>(&'a self, path: &'a [&'a str]) -> impl Iterator<Item = &'a Section> {
+        self.sections
+            .iter()
+            .filter(move |section| section.path == path)
+    }
+
+    fn section_exists(&self, path: &[String]) -> bool {
+        self.sections
+            .iter()
+            .any(|section| !section.array && section.path == path)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Document> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl fmt::Display) -> anyhow::Error {
+        anyhow!("line {}: {}", self.line, message)
+    }
+
+    // Spaces and tabs only
+    fn skip_blank(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+    }
+
+    // Whitespace, newlines and comments, used inside arrays and between statements
+    fn skip_space(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.next();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.next();
+        }
+    }
+
+    // Nothing but a comment may follow a statement on its line
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_blank();
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.chars.get(self.pos + 1) == Some(&'\n') => Ok(()),
+            Some('#') => {
+                self.skip_comment();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("unexpected {:?}", c))),
+        }
+    }
+
+    fn document(&mut self) -> Result<Document> {
+        let mut document = Document::default();
+
+        loop {
+            self.skip_space();
+            match self.peek() {
+                None => return Ok(document),
+                Some('[') => {
+                    let line = self.line;
+                    self.next();
+                    let array = self.peek() == Some('[');
+                    if array {
+                        self.next();
+                    }
+
+                    let path = self.key_path()?;
+                    for _ in 0..if array { 2 } else { 1 } {
+                        if self.next() != Some(']') {
+                            return Err(self.error("expected ] to close the header"));
+                        }
+                    }
+                    self.end_of_line()?;
+
+                    if !array && document.section_exists(&path) {
+                        bail!("line {}: [{}] is defined twice", line, path.join("."));
+                    }
+                    document.sections.push(Section {
+                        path,
+                        array,
+                        line,
+                        table: Table::default(),
+                    });
+                }
+                Some(_) => {
+                    let line = self.line;
+                    let key = self.key()?;
+                    self.skip_blank();
+                    if self.next() != Some('=') {
+                        return Err(self.error(format!("expected = after {}", key)));
+                    }
+                    self.skip_blank();
+                    let value = self.value()?;
+                    self.end_of_line()?;
+
+                    let table = match document.sections.last_mut() {
+                        Some(section) => &mut section.table,
+                        None => &mut document.root,
+                    };
+                    if table.get(&key).is_some() {
+                        bail!("line {}: {} is set twice", line, key);
+                    }
+                    table.entries.push(Entry { key, value, line });
+                }
+            }
+        }
+    }
+
+    fn key_path(&mut self) -> Result<Vec<String>> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_blank();
+            path.push(self.key()?);
+            self.skip_blank();
+            if self.peek() != Some('.') {
+                return Ok(path);
+            }
+            self.next();
+        }
+    }
+
+    fn key(&mut self) -> Result<String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.next();
+                }
+                if self.pos == start {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => {
+                self.next();
+                let mut values = Vec::new();
+                loop {
+                    self.skip_space();
+                    if self.peek() == Some(']') {
+                        self.next();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_space();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err(self.error("expected , or ] in array")),
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.')
+                }) {
+                    self.next();
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+
+                match word.as_str() {
+                    "" => Err(self.error("expected a value")),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => parse_number(&word)
+                        .map(Value::Number)
+                        .ok_or_else(|| self.error(format!("invalid value {}", word))),
+                }
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        let line = self.line;
+        self.next();
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => bail!("line {}: unterminated string", line),
+                Some('"') => return Ok(text),
+                Some('\\') => text.push(match self.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some(c) => return Err(self.error(format!("unknown escape \\{}", c))),
+                    None => bail!("line {}: unterminated string", line),
+                }),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        let line = self.line;
+        self.next();
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => bail!("line {}: unterminated string", line),
+                Some('\'') => return Ok(text),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+}
+
+// Decimal, float or 0x-prefixed hex, with optional _ separators. Infinities and NaN, including
+// floats too large to represent such as 1e999, are rejected.
+fn parse_number(word: &str) -> Option<f64> {
+    let word = word.replace('_', "");
+    if let Some(hex) = word.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16).ok().map(|n| n as f64);
+    }
+    word.parse::<f64>().ok().filter(|number| number.is_finite())
+}
@@ -0,0 +1,144 @@
+use crate::{emulator::Emulator, gameboy::vram::VramSnapshot};
+
+// Instructions shown before and after PC in a snapshot
+const DISASSEMBLY_BEFORE: usize = 4;
+const DISASSEMBLY_AFTER: usize = 8;
+
+// Bytes in a snapshot's memory page
+pub const MEMORY_PAGE: u16 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Register {
+    pub name: &'static str,
+    // 8 or 16
+    pub bits: u32,
+    pub value: u16,
+}
+
+// What a debugger needs from a core on top of running it: its registers, its memory as the
+// CPU sees it and where it is. Used by the GDB stub, see `gdb::GdbServer`.
+pub trait DebugTarget: Emulator {
+    // Every register, always in the same order, which is the order debuggers number them in
+    fn register_file(&self) -> Vec<Register>;
+    // Returns false if there is no register `index`
+    fn set_register(&mut self, index: usize, value: u16) -> bool;
+    fn pc(&self) -> u16;
+    // The instruction at `addr` and its length in bytes
+    fn disassemble(&self, addr: u16) -> (String, u16);
+    // Lines of state beyond the registers, such as the stack or I/O registers
+    fn status(&self) -> Vec<String>;
+    // None where nothing is mapped
+    fn read_memory(&self, addr: u16) -> Option<u8>;
+    // Returns false where memory can't be written, such as ROM
+    fn write_memory(&mut self, addr: u16, value: u8) -> bool;
+    // Decoded tiles, tilemaps and sprites, for cores with GameBoy-style video memory
+    fn vram(&self) -> Option<VramSnapshot> {
+        None
+    }
+    // Byte order of multi-byte registers and memory
+    fn big_endian(&self) -> bool {
+        false
+    }
+}
+
+// Register `value` as bytes in the target's byte order
+pub fn register_bytes(register: &Register, big_endian: bool) -> Vec<u8> {
+    let bytes = if big_endian {
+        register.value.to_be_bytes()
+    } else {
+        register.value.to_le_bytes()
+    };
+    match register.bits {
+        8 => vec![register.value as u8],
+        _ => bytes.to_vec(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisassemblyLine {
+    pub addr: u16,
+    pub text: String,
+    // The instruction at PC
+    pub current: bool,
+}
+
+// A copy of what the debug overlay shows, taken on the emulation thread after a frame
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugSnapshot {
+    pub registers: Vec<Register>,
+    pub status: Vec<String>,
+    pub disassembly: Vec<DisassemblyLine>,
+    pub memory_start: u16,
+    // `MEMORY_PAGE` bytes from `memory_start`, None where nothing is mapped
+    pub memory: Vec<Option<u8>>,
+    pub vram: Option<VramSnapshot>,
+}
+
+impl DebugSnapshot {
+    pub fn capture(target: &dyn DebugTarget, memory_start: u16) -> Self {
+        let memory = (0..MEMORY_PAGE)
+            .map(|offset| {
+                memory_start
+                    .checked_add(offset)
+                    .and_then(|addr| target.read_memory(addr))
+            })
+            .collect();
+        Self {
+            registers: target.register_file(),
+            status: target.status(),
+            disassembly: disassembly_around(target, DISASSEMBLY_BEFORE, DISASSEMBLY_AFTER),
+            memory_start,
+            memory,
+            vram: target.vram(),
+        }
+    }
+}
+
+// Instructions around PC. Variable-length instructions can't be decoded backwards, so the ones
+// before PC come from decoding forwards from a little earlier, starting from the furthest
+// address that lines up with PC. Data just before PC can make them wrong.
+pub fn disassembly_around(
+    target: &dyn DebugTarget,
+    before: usize,
+    after: usize,
+) -> Vec<DisassemblyLine> {
+    let pc = target.pc();
+    let decode = |addr: u16| {
+        let (text, len) = target.disassemble(addr);
+        let line = DisassemblyLine {
+            addr,
+            text,
+            current: addr == pc,
+        };
+        (line, len.max(1))
+    };
+
+    let mut lines = Vec::new();
+    // Enough bytes for `before` instructions of up to 4 bytes
+    for back in (1..=before as u16 * 4).rev() {
+        let Some(mut addr) = pc.checked_sub(back) else {
+            continue;
+        };
+        let mut decoded = Vec::new();
+        while addr < pc {
+            let (line, len) = decode(addr);
+            decoded.push(line);
+            addr = addr.saturating_add(len);
+        }
+        if addr == pc {
+            lines = decoded.split_off(decoded.len().saturating_sub(before));
+            break;
+        }
+    }
+
+    let mut addr = pc;
+    for _ in 0..=after {
+        let (line, len) = decode(addr);
+        lines.push(line);
+        let Some(next) = addr.checked_add(len) else {
+            break;
+        };
+        addr = next;
+    }
+    lines
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+
+use crate::{keymap::Keymap, palette::Palette, recording::RecordingFormat};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisplaySettings {
+    // Only scale the screen by whole multiples so every emulated pixel is the same size
+    pub integer_scaling: bool,
+    // sRGB color of the bars around the screen
+    pub border_color: [u8; 3],
+    pub filter: ScreenFilter,
+    // Name of the post-processing preset, None for the plain screen
+    pub shader: Option<String>,
+    // Share of the previous frame kept in each new one, None to not blend frames
+    pub frame_blend: Option<f32>,
+    // Directory of extra presets, see `postprocess`
+    pub shader_dir: Option<PathBuf>,
+    // Palettes cycled through with F5, and the one the core starts with
+    pub palettes: Vec<Palette>,
+    pub palette: usize,
+    // Where F12 saves screenshots and F9 recordings, the working directory when empty
+    pub screenshot_dir: PathBuf,
+    pub recording_format: RecordingFormat,
+    // Host keys for the core's buttons
+    pub keymap: Keymap,
+}
+
+// How the screen texture is sampled when scaled up
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ScreenFilter {
+    // Crisp, square pixels
+    #[default]
+    Nearest,
+    // Smoothed, mostly useful with fractional scaling
+    Bilinear,
+}
+
+impl ScreenFilter {
+    pub fn toggled(self) -> Self {
+        match self {
+            ScreenFilter::Nearest => ScreenFilter::Bilinear,
+            ScreenFilter::Bilinear => ScreenFilter::Nearest,
+        }
+    }
+
+    pub fn filter_mode(self) -> wgpu::FilterMode {
+        match self {
+            ScreenFilter::Nearest => wgpu::FilterMode::Nearest,
+            ScreenFilter::Bilinear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+// Area of the window the screen is drawn into, in physical pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// Largest viewport with the source's aspect ratio that fits the target, centred.
+// With integer scaling the scale is rounded down to a whole number, unless the target is too
+// small for even 1x, in which case it shrinks to fit like the fractional mode.
+pub fn fit_viewport(source: (u32, u32), target: (u32, u32), integer_scaling: bool) -> Viewport {
+    let (source_width, source_height) = (source.0.max(1) as f32, source.1.max(1) as f32);
+    let (target_width, target_height) = (target.0 as f32, target.1 as f32);
+
+    let mut scale = (target_width / source_width).min(target_height / source_height);
+    if integer_scaling && scale >= 1.0 {
+        scale = scale.floor();
+    }
+
+    let width = source_width * scale;
+    let height = source_height * scale;
+    Viewport {
+        x: ((target_width - width) / 2.0).floor(),
+        y: ((target_height - height) / 2.0).floor(),
+        width,
+        height,
+    }
+}
+
+// Parses `#RRGGBB` or `RRGGBB`
+pub fn parse_hex_color(text: &str) -> Result<[u8; 3]> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        bail!("Expected a color like #RRGGBB, got {:?}", text);
+    }
+
+    let mut color = [0u8; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+        *value = u8::from_str_radix(&hex[channel * 2..channel * 2 + 2], 16)
+            .with_context(|| format!("Expected a color like #RRGGBB, got {:?}", text))?;
+    }
+    Ok(color)
+}
+
+// The clear color is given to wgpu in linear space when the surface is sRGB
+pub fn clear_color(color: [u8; 3], srgb_surface: bool) -> wgpu::Color {
+    let channel = |value: u8| {
+        let value = value as f64 / 255.0;
+        if !srgb_surface {
+            value
+        } else if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    wgpu::Color {
+        r: channel(color[0]),
+        g: channel(color[1]),
+        b: channel(color[2]),
+        a: 1.0,
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    debug::DebugSnapshot,
+    emulator::{EmulatorDevice, FrameView},
+    frame_buffer::{FrameReader, FrameWriter, frame_buffer},
+    logging::STATUS_TARGET,
+    palette::Palette,
+    recording::Recorder,
+    scheduler::Scheduler,
+};
+
+// If the thread falls further behind than this it stops trying to catch up
+const MAX_FRAME_LAG: u32 = 4;
+
+// How often the measured speed is updated
+const SPEED_SAMPLE_PERIOD: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed {
+    // Fraction of the core's real speed, e.g. 0.5 for slow motion or 4.0 for fast-forward
+    Multiplier(f64),
+    // As fast as the host can run it
+    Uncapped,
+}
+
+pub enum EmulatorCommand {
+    // Presses or releases one of `Emulator::buttons`
+    Button(usize, bool),
+    SetSpeed(Speed),
+    SetPaused(bool),
+    SetPalette(Palette),
+    // Records every frame from now on to a .gif or .y4m file
+    StartRecording(PathBuf),
+    StopRecording,
+    // Runs one frame, pausing first if needed
+    AdvanceFrame,
+    // Publishes a `DebugSnapshot` after every frame with the memory page at the address, or
+    // stops with None
+    SetDebugView(Option<u16>),
+    Quit,
+}
+
+// Runs a core on its own thread in frame-sized batches, paced to real time
+pub struct EmulationThread {
+    commands: Sender<EmulatorCommand>,
+    frames: FrameReader,
+    measured_speed: Arc<AtomicU64>,
+    emulated_frames: Arc<AtomicU64>,
+    recording: Arc<AtomicBool>,
+    debug_snapshot: Arc<Mutex<Option<DebugSnapshot>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EmulationThread {
+    pub fn spawn(emulator: EmulatorDevice) -> Self {
+        let (commands, command_receiver) = mpsc::channel();
+        let (frame_writer, frames) = frame_buffer();
+        let measured_speed = Arc::new(AtomicU64::new(0f64.to_bits()));
+        let emulated_frames = Arc::new(AtomicU64::new(0));
+        let recording = Arc::new(AtomicBool::new(false));
+
+        let debug_snapshot = Arc::new(Mutex::new(None));
+
+        let thread_speed = Arc::clone(&measured_speed);
+        let thread_frames = Arc::clone(&emulated_frames);
+        let thread_recording = Arc::clone(&recording);
+        let thread_snapshot = Arc::clone(&debug_snapshot);
+        let handle = thread::Builder::new()
+            .name("emulation".to_string())
+            .spawn(move || {
+                run(
+                    emulator,
+                    command_receiver,
+                    frame_writer,
+                    thread_speed,
+                    thread_frames,
+                    thread_recording,
+                    thread_snapshot,
+                )
+            })
+            .expect("Could not spawn emulation thread");
+
+        Self {
+            commands,
+            frames,
+            measured_speed,
+            emulated_frames,
+            recording,
+            debug_snapshot,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn set_button(&self, button: usize, is_pressed: bool) {
+        self.send(EmulatorCommand::Button(button, is_pressed));
+    }
+
+    pub fn send(&self, command: EmulatorCommand) {
+        let _ = self.commands.send(command);
+    }
+
+    pub fn frames(&mut self) -> &mut FrameReader {
+        &mut self.frames
+    }
+
+    // Emulated time per real time over the last half second, 1.0 being full speed
+    pub fn measured_speed(&self) -> f64 {
+        f64::from_bits(self.measured_speed.load(Ordering::Relaxed))
+    }
+
+    // Frames run since the thread started, including ones that left the picture unchanged
+    pub fn emulated_frames(&self) -> u64 {
+        self.emulated_frames.load(Ordering::Relaxed)
+    }
+
+    // Whether a recorder is running, which lags `StartRecording` and `StopRecording` until the
+    // thread gets to them, and turns off if the recording could not be started
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    // The snapshot published since the last call, if any, see `EmulatorCommand::SetDebugView`
+    pub fn take_debug_snapshot(&self) -> Option<DebugSnapshot> {
+        self.debug_snapshot.lock().ok()?.take()
+    }
+}
+
+impl Drop for EmulationThread {
+    fn drop(&mut self) {
+        self.send(EmulatorCommand::Quit);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Control {
+    speed: Speed,
+    paused: bool,
+    frames_to_advance: u32,
+    quit: bool,
+    frame_rate: f64,
+    recorder: Option<Recorder>,
+    // Mirrors whether `recorder` is set, for `EmulationThread::is_recording`
+    recording: Arc<AtomicBool>,
+    debug_view: Option<u16>,
+    debug_snapshot: Arc<Mutex<Option<DebugSnapshot>>>,
+}
+
+impl Control {
+    fn apply(&mut self, command: EmulatorCommand, emulator: &mut EmulatorDevice) {
+        match command {
+            EmulatorCommand::Button(button, is_pressed) => {
+                emulator.device.set_button(button, is_pressed)
+            }
+            EmulatorCommand::SetSpeed(speed) => self.speed = speed,
+            EmulatorCommand::SetPaused(paused) => self.paused = paused,
+            EmulatorCommand::SetPalette(palette) => emulator.device.set_palette(&palette),
+            EmulatorCommand::AdvanceFrame => {
+                self.paused = true;
+                self.frames_to_advance += 1;
+            }
+            EmulatorCommand::StartRecording(path) => {
+                self.stop_recording();
+                match Recorder::create(&path, self.frame_rate) {
+                    Ok(recorder) => {
+                        log::info!(target: STATUS_TARGET, "Recording to {}", path.display());
+                        self.recorder = Some(recorder);
+                        self.recording.store(true, Ordering::Relaxed);
+                    }
+                    Err(error) => log::error!("{:#}", error),
+                }
+            }
+            EmulatorCommand::StopRecording => self.stop_recording(),
+            EmulatorCommand::SetDebugView(view) => {
+                self.debug_view = view;
+                // Straight away, as a paused core won't run another frame
+                self.publish_debug_snapshot(emulator);
+            }
+            EmulatorCommand::Quit => self.quit = true,
+        }
+    }
+
+    fn record(&mut self, frame: FrameView, audio: &[f32]) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push_frame(frame);
+            recorder.push_audio(audio);
+        }
+    }
+
+    fn publish_debug_snapshot(&self, emulator: &EmulatorDevice) {
+        let Some(memory_start) = self.debug_view else {
+            return;
+        };
+        let Some(target) = emulator.device.debug_target() else {
+            return;
+        };
+        let snapshot = DebugSnapshot::capture(target, memory_start);
+        if let Ok(mut slot) = self.debug_snapshot.lock() {
+            *slot = Some(snapshot);
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+        self.recording.store(false, Ordering::Relaxed);
+        match recorder.finish() {
+            Ok(path) => log::info!(target: STATUS_TARGET, "Saved {}", path.display()),
+            Err(error) => log::error!("Recording failed: {:#}", error),
+        }
+    }
+}
+
+fn run(
+    emulator: EmulatorDevice,
+    commands: Receiver<EmulatorCommand>,
+    frames: FrameWriter,
+    measured_speed: Arc<AtomicU64>,
+    emulated_frames: Arc<AtomicU64>,
+    recording: Arc<AtomicBool>,
+    debug_snapshot: Arc<Mutex<Option<DebugSnapshot>>>,
+) {
+    let scheduler = Scheduler::new(emulator.device.clock());
+    let mut control = Control {
+        speed: Speed::Multiplier(1.0),
+        paused: false,
+        frames_to_advance: 0,
+        quit: false,
+        frame_rate: scheduler.clock().frame_rate(),
+        recorder: None,
+        recording,
+        debug_view: None,
+        debug_snapshot,
+    };
+
+    run_frames(
+        emulator,
+        scheduler,
+        &mut control,
+        commands,
+        frames,
+        measured_speed,
+        emulated_frames,
+    );
+
+    // However the loop ended, leave a playable file behind
+    control.stop_recording();
+}
+
+fn run_frames(
+    mut emulator: EmulatorDevice,
+    mut scheduler: Scheduler,
+    control: &mut Control,
+    commands: Receiver<EmulatorCommand>,
+    mut frames: FrameWriter,
+    measured_speed: Arc<AtomicU64>,
+    emulated_frames: Arc<AtomicU64>,
+) {
+    let frame_rate = control.frame_rate;
+    let frame_duration = Duration::from_secs_f64(1.0 / frame_rate);
+    let mut audio = Vec::new();
+
+    let mut deadline = Instant::now();
+    let mut sample_start = Instant::now();
+    let mut sample_frames = 0u32;
+
+    loop {
+        if control.paused && control.frames_to_advance == 0 {
+            // Nothing to run, so block until the frontend asks for something
+            match commands.recv() {
+                Ok(command) => control.apply(command, &mut emulator),
+                Err(_) => return,
+            }
+            deadline = Instant::now();
+            sample_start = Instant::now();
+            sample_frames = 0;
+            measured_speed.store(0f64.to_bits(), Ordering::Relaxed);
+        }
+
+        let speed = control.speed;
+        loop {
+            match commands.try_recv() {
+                Ok(command) => control.apply(command, &mut emulator),
+                Err(TryRecvError::Disconnected) => return,
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        if control.quit {
+            return;
+        }
+        if control.speed != speed {
+            deadline = Instant::now();
+        }
+        if control.paused {
+            if control.frames_to_advance == 0 {
+                continue;
+            }
+            control.frames_to_advance -= 1;
+        }
+
+        scheduler.run_frame(emulator.device.as_mut());
+        emulated_frames.fetch_add(1, Ordering::Relaxed);
+
+        if emulator.device.take_frame_ready() {
+            let view = emulator.device.framebuffer();
+            frames.write(|frame| {
+                // Reuses the slot's allocation once it has grown to the frame size
+                frame.data.clear();
+                frame.data.extend_from_slice(view.data);
+                frame.width = view.width;
+                frame.height = view.height;
+                frame.format = view.format;
+            });
+        }
+
+        // Drained even when not recording so it does not pile up in the core
+        audio.clear();
+        emulator.device.drain_audio(&mut audio);
+        control.record(emulator.device.framebuffer(), &audio);
+        control.publish_debug_snapshot(&emulator);
+
+        sample_frames += 1;
+        let elapsed = sample_start.elapsed();
+        if elapsed >= SPEED_SAMPLE_PERIOD {
+            let speed = sample_frames as f64 / elapsed.as_secs_f64() / frame_rate;
+            measured_speed.store(speed.to_bits(), Ordering::Relaxed);
+            sample_start = Instant::now();
+            sample_frames = 0;
+        }
+
+        if control.paused {
+            continue;
+        }
+        let Speed::Multiplier(multiplier) = control.speed else {
+            continue;
+        };
+
+        deadline += frame_duration.div_f64(multiplier);
+        let now = Instant::now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        } else if now - deadline > frame_duration * MAX_FRAME_LAG {
+            deadline = now;
+        }
+    }
+}
@@ -1,12 +1,83 @@
+use crate::{debug::DebugTarget, palette::Palette};
+
+// Rate of the mono samples cores hand out through `drain_audio`
+pub const AUDIO_SAMPLE_RATE: u32 = 44_100;
+
 pub trait Emulator: Send {
-    fn tick(&mut self);
-    fn get_vbuf(&mut self) -> (Vec<u8>, usize, usize);
+    // Runs one instruction and returns how many master clock cycles it took
+    fn tick(&mut self) -> u32;
+    fn clock(&self) -> MachineClock;
+    // The core's own framebuffer, borrowed rather than copied
+    fn framebuffer(&self) -> FrameView<'_>;
+    // Whether the framebuffer changed since the last call
+    fn take_frame_ready(&mut self) -> bool;
     fn timer_tick(&mut self);
-    fn handle_key(&mut self, code: winit::keyboard::KeyCode, is_pressed: bool);
+    // Names of the core's buttons, such as a keypad key or a joypad button. The frontend binds
+    // host keys to them by name, see `keymap::Keymap`
+    fn buttons(&self) -> &'static [&'static str] {
+        &[]
+    }
+    // Presses or releases `buttons()[button]`
+    fn set_button(&mut self, _button: usize, _is_pressed: bool) {}
+    // Colors for monochrome cores, ignored by cores with their own colors
+    fn set_palette(&mut self, _palette: &Palette) {}
+    // Moves the audio produced since the last call onto the end of `samples`
+    fn drain_audio(&mut self, _samples: &mut Vec<f32>) {}
+    // Registers and memory for the debug overlay, for cores that support it
+    fn debug_target(&self) -> Option<&dyn DebugTarget> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+    // 4 bytes per pixel in R, G, B, A order, rows tightly packed
+    #[default]
+    Rgba8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8 => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FrameView<'a> {
+    pub data: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+}
+
+// Timing a core declares about itself. Events are counted in master clock cycles so the ratio
+// of instructions to timer ticks is the same on every run and every machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MachineClock {
+    // Master clock in cycles per second
+    pub hz: u64,
+    // Cycles between calls to `timer_tick`
+    pub timer_period: u64,
+    // Cycles between frames (vblank)
+    pub frame_period: u64,
+}
+
+impl MachineClock {
+    pub fn frame_rate(&self) -> f64 {
+        self.hz as f64 / self.frame_period as f64
+    }
 }
 
 pub struct EmulatorDevice {
     pub device: Box<dyn Emulator + Send>,
-    pub cpu_hz: f64,
-    pub timer_hz: f64,
+}
+
+impl EmulatorDevice {
+    pub fn new(device: impl Emulator + 'static) -> Self {
+        Self {
+            device: Box::new(device),
+        }
+    }
 }
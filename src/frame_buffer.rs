@@ -0,0 +1,93 @@
+use crate::emulator::PixelFormat;
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+// Lock-free handoff of finished frames from the emulation thread to the renderer.
+//
+// The writer and the reader each own one slot, and a third slot sits in between. Publishing
+// swaps the writer's slot with the middle one, reading swaps the reader's slot with it, so
+// neither side ever waits on the other and the reader always sees the newest complete frame.
+
+const INDEX_MASK: usize = 0b11;
+const NEW_FRAME: usize = 0b100;
+
+#[derive(Clone, Default)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+}
+
+struct Shared {
+    slots: [UnsafeCell<Frame>; 3],
+    // Index of the middle slot, plus NEW_FRAME if the writer published since the last read
+    middle: AtomicUsize,
+}
+
+// Each slot is only ever accessed by whoever currently owns its index
+unsafe impl Sync for Shared {}
+
+pub struct FrameWriter {
+    shared: Arc<Shared>,
+    back: usize,
+}
+
+pub struct FrameReader {
+    shared: Arc<Shared>,
+    front: usize,
+}
+
+pub fn frame_buffer() -> (FrameWriter, FrameReader) {
+    let shared = Arc::new(Shared {
+        slots: Default::default(),
+        middle: AtomicUsize::new(1),
+    });
+
+    (
+        FrameWriter {
+            shared: Arc::clone(&shared),
+            back: 0,
+        },
+        FrameReader { shared, front: 2 },
+    )
+}
+
+impl FrameWriter {
+    // Fills the back slot and publishes it as the newest frame
+    pub fn write(&mut self, fill: impl FnOnce(&mut Frame)) {
+        // SAFETY: `back` is owned by the writer until it is swapped into the middle below
+        fill(unsafe { &mut *self.shared.slots[self.back].get() });
+
+        let previous = self
+            .shared
+            .middle
+            .swap(self.back | NEW_FRAME, Ordering::AcqRel);
+        self.back = previous & INDEX_MASK;
+    }
+}
+
+impl FrameReader {
+    // The newest frame if one was published since the last call
+    pub fn read(&mut self) -> Option<&Frame> {
+        if self.shared.middle.load(Ordering::Acquire) & NEW_FRAME == 0 {
+            return None;
+        }
+
+        let previous = self.shared.middle.swap(self.front, Ordering::AcqRel);
+        self.front = previous & INDEX_MASK;
+
+        Some(self.latest())
+    }
+
+    // The frame returned by the last successful `read`
+    pub fn latest(&self) -> &Frame {
+        // SAFETY: `front` is owned by the reader until it is swapped into the middle
+        unsafe { &*self.shared.slots[self.front].get() }
+    }
+}
@@ -1,4 +1,35 @@
-use crate::emulator::Emulator;
+use std::fs;
+
+use crate::{
+    debug::{DebugTarget, Register},
+    emulator::{Emulator, FrameView, MachineClock, PixelFormat},
+    palette::Palette,
+};
+
+use self::{
+    trace::{TRACE_TARGET, doctor_line},
+    vram::VramSnapshot,
+};
+
+pub mod disasm;
+pub mod test_rom;
+pub mod trace;
+pub mod vram;
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+const GB_CLOCK_HZ: u64 = 4_194_304;
+// DIV increments at 16384 Hz
+const GB_DIV_PERIOD: u64 = 256;
+// 154 scanlines of 456 cycles
+const GB_FRAME_PERIOD: u64 = 70_224;
+
+// Joypad buttons in the order of their bits in `joypad`: the action buttons P1 reads with bit
+// 5 low, then the directions it reads with bit 4 low
+const GB_BUTTONS: [&str; 8] = ["A", "B", "Select", "Start", "Right", "Left", "Up", "Down"];
+
+const GB_SCREEN_WIDTH: usize = 160;
+const GB_SCREEN_HEIGHT: usize = 144;
 
 pub struct GameBoy {
     // Register File
@@ -7,92 +38,158 @@ pub struct GameBoy {
     a: u8,
     f: u8,
     regs: [u8; 8],
-    ir: u8,
-    ie: u8,
 
     // Memory
-    ram: [u8; 4096],
+    rom: Vec<u8>,
+    rom_bank: usize,
+    mbc1: bool,
+    memory: Box<[u8; 0x10000]>,
+
+    // Video
+    framebuffer: Box<[u8; GB_SCREEN_WIDTH * GB_SCREEN_HEIGHT * 4]>,
+    frame_ready: bool,
+    // RGB for each of the DMG's four shades
+    palette: [[u8; 3]; 4],
+
+    // Input, a set bit for each pressed button of GB_BUTTONS
+    joypad: u8,
+
+    // Debugging
+    serial_output: Vec<u8>,
+    software_breakpoint: bool,
 }
 
 impl GameBoy {
-    pub fn new() -> Self {
-        let gameboy = GameBoy::default();
+    pub fn new(filename: &str) -> Self {
+        let bytes: Vec<u8> = fs::read(filename).expect("Could not get file");
+
+        GameBoy::from_rom(&bytes)
+    }
+
+    pub fn from_rom(bytes: &[u8]) -> Self {
+        GameBoy {
+            rom: bytes.to_vec(),
+            mbc1: matches!(bytes.get(0x147), Some(0x01..=0x03)),
+            ..Default::default()
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+        match addr {
+            0x0000..0x4000 => self.rom.get(addr).copied().unwrap_or(0xFF),
+            0x4000..0x8000 => self
+                .rom
+                .get(self.rom_bank * ROM_BANK_SIZE + addr - ROM_BANK_SIZE)
+                .copied()
+                .unwrap_or(0xFF),
+            0xFF00 => self.p1(),
+            _ => self.memory[addr],
+        }
+    }
+
+    // The selected button groups, with pressed buttons reading as 0
+    fn p1(&self) -> u8 {
+        let select = self.memory[0xFF00] & 0x30;
+        let mut pressed = 0;
+        if select & 0x20 == 0 {
+            pressed |= self.joypad & 0x0F;
+        }
+        if select & 0x10 == 0 {
+            pressed |= self.joypad >> 4;
+        }
+        0xC0 | select | (!pressed & 0x0F)
+    }
 
-        gameboy
+    pub fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x2000..0x4000 if self.mbc1 => {
+                self.rom_bank = ((val & 0x1F) as usize).max(1);
+            }
+            0x0000..0x8000 => {}
+            // Only the group select bits are writable
+            0xFF00 => self.memory[0xFF00] = val & 0x30,
+            0xFF02 if val & 0x81 == 0x81 => {
+                // Serial transfer with internal clock, completes immediately
+                self.serial_output.push(self.memory[0xFF01]);
+                self.memory[0xFF02] = val & 0x7F;
+            }
+            _ => self.memory[addr as usize] = val,
+        }
+    }
+
+    // Bytes sent over the serial port since power on
+    pub fn serial_output(&self) -> &[u8] {
+        &self.serial_output
+    }
+
+    // LD B,B is used as a software breakpoint by test ROMs. Returns whether one was hit
+    // since the last call.
+    pub fn take_software_breakpoint(&mut self) -> bool {
+        std::mem::take(&mut self.software_breakpoint)
     }
 
     // Registers
-    fn B(&self) -> u8 {
+    fn b(&self) -> u8 {
         self.regs[0]
     }
-    fn C(&self) -> u8 {
+    fn c(&self) -> u8 {
         self.regs[1]
     }
-    fn BC(&self) -> u16 {
+    fn bc(&self) -> u16 {
         (self.regs[0] as u16) << 8 | (self.regs[1] as u16)
     }
-    fn D(&self) -> u8 {
+    fn d(&self) -> u8 {
         self.regs[2]
     }
-    fn E(&self) -> u8 {
+    fn e(&self) -> u8 {
         self.regs[3]
     }
-    fn DE(&self) -> u16 {
+    fn de(&self) -> u16 {
         (self.regs[2] as u16) << 8 | (self.regs[3] as u16)
     }
-    fn H(&self) -> u8 {
+    fn h(&self) -> u8 {
         self.regs[4]
     }
-    fn L(&self) -> u8 {
+    fn l(&self) -> u8 {
         self.regs[5]
     }
-    fn HL(&self) -> u16 {
+    fn hl(&self) -> u16 {
         (self.regs[4] as u16) << 8 | (self.regs[5] as u16)
     }
-    fn setB(&mut self, b: u8) {
-        self.regs[0] = b;
-    }
-    fn setC(&mut self, c: u8) {
-        self.regs[1] = c;
-    }
-    fn setBC(&mut self, bc: u16) {
-        self.regs[0] = ((bc >> 8) & 0xf) as u8;
-        self.regs[1] = (bc & 0xf) as u8;
-    }
-    fn setD(&mut self, d: u8) {
-        self.regs[2] = d;
-    }
-    fn setE(&mut self, e: u8) {
-        self.regs[3] = e;
-    }
-    fn setDE(&mut self, de: u16) {
-        self.regs[2] = ((de >> 8) & 0xf) as u8;
-        self.regs[3] = (de & 0xf) as u8;
-    }
-    fn setH(&mut self, h: u8) {
-        self.regs[4] = h;
-    }
-    fn setL(&mut self, l: u8) {
-        self.regs[5] = l;
-    }
-    fn setHL(&mut self, hl: u16) {
-        self.regs[4] = ((hl >> 8) & 0xf) as u8;
-        self.regs[5] = (hl & 0xf) as u8;
-    }
 }
 
 impl Default for GameBoy {
     fn default() -> Self {
+        // Register values left behind by the DMG boot ROM
         Self {
-            ..Default::default()
+            pc: 0x100,
+            sp: 0xFFFE,
+            a: 0x01,
+            f: 0xB0,
+            regs: [0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D, 0x00, 0x00],
+            rom: Vec::new(),
+            rom_bank: 1,
+            mbc1: false,
+            memory: Box::new([0u8; 0x10000]),
+            // Blank (white) LCD until there is a PPU to draw into it
+            framebuffer: Box::new([0xFF; GB_SCREEN_WIDTH * GB_SCREEN_HEIGHT * 4]),
+            frame_ready: true,
+            palette: [[0xFF; 3], [0xAA; 3], [0x55; 3], [0x00; 3]],
+            joypad: 0,
+            serial_output: Vec::new(),
+            software_breakpoint: false,
         }
     }
 }
 
 impl Emulator for GameBoy {
-    fn tick(&mut self) {
-        let opcode = self.ram[self.pc as usize];
-        self.pc += 1;
+    fn tick(&mut self) -> u32 {
+        if log::log_enabled!(target: TRACE_TARGET, log::Level::Trace) {
+            log::trace!(target: TRACE_TARGET, "{}", doctor_line(self));
+        }
+        let opcode = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
         let top_two = opcode >> 6;
         match top_two {
             0b00 => {
@@ -101,24 +198,32 @@ impl Emulator for GameBoy {
 
                 if yyy == 0b110 && xxx != 0b110 {
                     // LD r, n
-                    self.regs[xxx as usize] = self.ram[self.pc as usize];
-                    self.pc += 1;
+                    self.regs[xxx as usize] = self.read(self.pc);
+                    self.pc = self.pc.wrapping_add(1);
+                    8
                 } else if xxx == 0b110 && yyy == 0b110 {
                     // LD (HL), n
-                    self.ram[self.HL() as usize] = self.ram[self.pc as usize];
-                    self.pc += 1;
+                    self.write(self.hl(), self.read(self.pc));
+                    self.pc = self.pc.wrapping_add(1);
+                    12
                 } else if xxx == 0b001 && yyy == 0b010 {
                     // LD A, (BC)
-                    self.a = self.ram[self.BC() as usize];
+                    self.a = self.read(self.bc());
+                    8
                 } else if xxx == 0b011 && yyy == 0b010 {
                     // LD A, (DE)
-                    self.a = self.ram[self.DE() as usize];
+                    self.a = self.read(self.de());
+                    8
                 } else if xxx == 0b000 && yyy == 0b010 {
                     // LD (BC), A
-                    self.ram[self.BC() as usize] = self.a;
+                    self.write(self.bc(), self.a);
+                    8
                 } else if xxx == 0b010 && yyy == 0b010 {
                     // LD (DE), A
-                    self.ram[self.DE() as usize] = self.a;
+                    self.write(self.de(), self.a);
+                    8
+                } else {
+                    4
                 }
             }
             0b01 => {
@@ -126,29 +231,201 @@ impl Emulator for GameBoy {
                 let yyy = opcode & 0b111;
                 if xxx == 0b110 {
                     // LD (HL), r
-                    self.ram[self.HL() as usize] = self.regs[xxx as usize];
+                    self.write(self.hl(), self.regs[yyy as usize]);
+                    8
                 } else if yyy == 0b110 {
                     // LD r, (HL)
-                    self.regs[xxx as usize] = self.ram[self.HL() as usize];
+                    self.regs[xxx as usize] = self.read(self.hl());
+                    8
                 } else {
                     // LD r, r'
                     self.regs[xxx as usize] = self.regs[yyy as usize];
+                    if opcode == 0x40 {
+                        self.software_breakpoint = true;
+                    }
+                    4
                 }
             }
-            _ => {}
+            _ => 4,
         }
     }
 
-    fn get_vbuf(&mut self) -> (Vec<u8>, usize, usize) {
-        // todo!()
-        (vec![0u8, 0u8, 0u8], 1, 1)
+    fn clock(&self) -> MachineClock {
+        MachineClock {
+            hz: GB_CLOCK_HZ,
+            timer_period: GB_DIV_PERIOD,
+            frame_period: GB_FRAME_PERIOD,
+        }
+    }
+
+    fn framebuffer(&self) -> FrameView<'_> {
+        FrameView {
+            data: &self.framebuffer[..],
+            width: GB_SCREEN_WIDTH,
+            height: GB_SCREEN_HEIGHT,
+            format: PixelFormat::Rgba8,
+        }
+    }
+
+    fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
     }
 
     fn timer_tick(&mut self) {
         // todo!()
     }
 
-    fn handle_key(&mut self, code: winit::keyboard::KeyCode, is_pressed: bool) {
-        // todo!()
+    fn buttons(&self) -> &'static [&'static str] {
+        &GB_BUTTONS
+    }
+
+    fn set_button(&mut self, button: usize, is_pressed: bool) {
+        if button >= GB_BUTTONS.len() {
+            return;
+        }
+        let bit = 1 << button;
+        if is_pressed {
+            // A press requests the joypad interrupt
+            if self.joypad & bit == 0 {
+                self.memory[0xFF0F] |= 0x10;
+            }
+            self.joypad |= bit;
+        } else {
+            self.joypad &= !bit;
+        }
+    }
+
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = palette.colors;
+
+        // Without a PPU the whole screen is shade 0
+        for pixel in self.framebuffer.chunks_exact_mut(4) {
+            pixel[..3].copy_from_slice(&self.palette[0]);
+        }
+        self.frame_ready = true;
+    }
+
+    fn debug_target(&self) -> Option<&dyn DebugTarget> {
+        Some(self)
+    }
+}
+
+const IO_REGISTER_GROUPS: [&[(&str, u16)]; 5] = [
+    &[
+        ("LCDC", 0xFF40),
+        ("STAT", 0xFF41),
+        ("LY", 0xFF44),
+        ("LYC", 0xFF45),
+    ],
+    &[
+        ("SCY", 0xFF42),
+        ("SCX", 0xFF43),
+        ("WY", 0xFF4A),
+        ("WX", 0xFF4B),
+    ],
+    &[
+        ("BGP", 0xFF47),
+        ("OBP0", 0xFF48),
+        ("OBP1", 0xFF49),
+        ("DMA", 0xFF46),
+    ],
+    &[
+        ("DIV", 0xFF04),
+        ("TIMA", 0xFF05),
+        ("TMA", 0xFF06),
+        ("TAC", 0xFF07),
+    ],
+    &[
+        ("IF", 0xFF0F),
+        ("IE", 0xFFFF),
+        ("P1", 0xFF00),
+        ("SC", 0xFF02),
+    ],
+];
+
+const REGISTER_NAMES: [&str; 8] = ["a", "f", "b", "c", "d", "e", "h", "l"];
+
+// A, F, B, C, D, E, H and L, then SP and PC
+impl DebugTarget for GameBoy {
+    fn register_file(&self) -> Vec<Register> {
+        let bytes = [
+            self.a,
+            self.f,
+            self.b(),
+            self.c(),
+            self.d(),
+            self.e(),
+            self.h(),
+            self.l(),
+        ];
+        let mut registers: Vec<Register> = REGISTER_NAMES
+            .iter()
+            .zip(bytes)
+            .map(|(name, value)| Register {
+                name,
+                bits: 8,
+                value: value as u16,
+            })
+            .collect();
+        for (name, value) in [("sp", self.sp), ("pc", self.pc)] {
+            registers.push(Register {
+                name,
+                bits: 16,
+                value,
+            });
+        }
+        registers
+    }
+
+    fn set_register(&mut self, index: usize, value: u16) -> bool {
+        match index {
+            0 => self.a = value as u8,
+            // The low nibble of F always reads 0
+            1 => self.f = value as u8 & 0xF0,
+            2..8 => self.regs[index - 2] = value as u8,
+            8 => self.sp = value,
+            9 => self.pc = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn disassemble(&self, addr: u16) -> (String, u16) {
+        disasm::disassemble(addr, |addr| self.read(addr))
+    }
+
+    // The I/O registers, grouped by what they control
+    fn status(&self) -> Vec<String> {
+        IO_REGISTER_GROUPS
+            .iter()
+            .map(|group| {
+                let registers: Vec<String> = group
+                    .iter()
+                    .map(|(name, addr)| format!("{}={:02X}", name, self.read(*addr)))
+                    .collect();
+                registers.join(" ")
+            })
+            .collect()
+    }
+
+    fn read_memory(&self, addr: u16) -> Option<u8> {
+        Some(self.read(addr))
+    }
+
+    fn vram(&self) -> Option<VramSnapshot> {
+        Some(self.vram_snapshot())
+    }
+
+    // Straight into memory, without the side effects of `write` such as ROM bank switches
+    fn write_memory(&mut self, addr: u16, value: u8) -> bool {
+        if addr < 0x8000 {
+            return false;
+        }
+        self.memory[addr as usize] = value;
+        true
     }
 }
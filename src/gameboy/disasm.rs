@@ -0,0 +1,101 @@
+// SM83 disassembler, decoding opcodes by their x/y/z bit fields (xx yyy zzz) the same way
+// `GameBoy::tick` does. Mnemonics are uppercase with memory operands in parentheses, matching
+// the comments in the core. Relative jumps show their absolute target, and the 11 unused
+// opcodes come out as `DB 0xNN`.
+
+const R: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const RP: [&str; 4] = ["BC", "DE", "HL", "SP"];
+// PUSH and POP use AF in place of SP
+const RP2: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const CC: [&str; 4] = ["NZ", "Z", "NC", "C"];
+const ALU: [&str; 8] = [
+    "ADD A, ", "ADC A, ", "SUB ", "SBC A, ", "AND ", "XOR ", "OR ", "CP ",
+];
+const ROT: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+const ACCUMULATOR_OPS: [&str; 8] = ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"];
+
+// Decodes the instruction at `addr`, returning its text and length in bytes. `read` is called
+// for the opcode and operand bytes only, so it can be a slice lookup or `GameBoy::read`.
+pub fn disassemble(addr: u16, read: impl Fn(u16) -> u8) -> (String, u16) {
+    let opcode = read(addr);
+    let d8 = || read(addr.wrapping_add(1));
+    let d16 = || u16::from_le_bytes([read(addr.wrapping_add(1)), read(addr.wrapping_add(2))]);
+    // Relative jumps are from the end of the two byte instruction
+    let relative = || addr.wrapping_add(2).wrapping_add(d8() as i8 as u16);
+
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 0b111) as usize;
+    let z = opcode & 0b111;
+    let p = y >> 1;
+    let q = y & 1;
+
+    let (text, len) = match (x, z) {
+        (0, 0) => match y {
+            0 => ("NOP".to_string(), 1),
+            1 => (format!("LD (0x{:04X}), SP", d16()), 3),
+            2 => ("STOP".to_string(), 2),
+            3 => (format!("JR 0x{:04X}", relative()), 2),
+            _ => (format!("JR {}, 0x{:04X}", CC[y - 4], relative()), 2),
+        },
+        (0, 1) if q == 0 => (format!("LD {}, 0x{:04X}", RP[p], d16()), 3),
+        (0, 1) => (format!("ADD HL, {}", RP[p]), 1),
+        (0, 2) => {
+            let memory = ["(BC)", "(DE)", "(HL+)", "(HL-)"][p];
+            match q {
+                0 => (format!("LD {}, A", memory), 1),
+                _ => (format!("LD A, {}", memory), 1),
+            }
+        }
+        (0, 3) if q == 0 => (format!("INC {}", RP[p]), 1),
+        (0, 3) => (format!("DEC {}", RP[p]), 1),
+        (0, 4) => (format!("INC {}", R[y]), 1),
+        (0, 5) => (format!("DEC {}", R[y]), 1),
+        (0, 6) => (format!("LD {}, 0x{:02X}", R[y], d8()), 2),
+        (0, _) => (ACCUMULATOR_OPS[y].to_string(), 1),
+
+        (1, 6) if y == 6 => ("HALT".to_string(), 1),
+        (1, _) => (format!("LD {}, {}", R[y], R[z as usize]), 1),
+
+        (2, _) => (format!("{}{}", ALU[y], R[z as usize]), 1),
+
+        (3, 0) => match y {
+            0..=3 => (format!("RET {}", CC[y]), 1),
+            4 => (format!("LDH (0xFF{:02X}), A", d8()), 2),
+            5 => (format!("ADD SP, {}", d8() as i8), 2),
+            6 => (format!("LDH A, (0xFF{:02X})", d8()), 2),
+            _ => (format!("LD HL, SP{:+}", d8() as i8), 2),
+        },
+        (3, 1) if q == 0 => (format!("POP {}", RP2[p]), 1),
+        (3, 1) => (["RET", "RETI", "JP HL", "LD SP, HL"][p].to_string(), 1),
+        (3, 2) => match y {
+            0..=3 => (format!("JP {}, 0x{:04X}", CC[y], d16()), 3),
+            4 => ("LD (C), A".to_string(), 1),
+            5 => (format!("LD (0x{:04X}), A", d16()), 3),
+            6 => ("LD A, (C)".to_string(), 1),
+            _ => (format!("LD A, (0x{:04X})", d16()), 3),
+        },
+        (3, 3) if y == 0 => (format!("JP 0x{:04X}", d16()), 3),
+        (3, 3) if y == 1 => (disassemble_cb(d8()), 2),
+        (3, 3) if y == 6 => ("DI".to_string(), 1),
+        (3, 3) if y == 7 => ("EI".to_string(), 1),
+        (3, 4) if y <= 3 => (format!("CALL {}, 0x{:04X}", CC[y], d16()), 3),
+        (3, 5) if q == 0 => (format!("PUSH {}", RP2[p]), 1),
+        (3, 5) if p == 0 => (format!("CALL 0x{:04X}", d16()), 3),
+        (3, 6) => (format!("{}0x{:02X}", ALU[y], d8()), 2),
+        (3, 7) => (format!("RST 0x{:02X}", y * 8), 1),
+        _ => (format!("DB 0x{:02X}", opcode), 1),
+    };
+    (text, len)
+}
+
+// The second byte of a CB-prefixed instruction
+pub fn disassemble_cb(opcode: u8) -> String {
+    let y = ((opcode >> 3) & 0b111) as usize;
+    let register = R[(opcode & 0b111) as usize];
+    match opcode >> 6 {
+        0 => format!("{} {}", ROT[y], register),
+        1 => format!("BIT {}, {}", y, register),
+        2 => format!("RES {}, {}", y, register),
+        _ => format!("SET {}, {}", y, register),
+    }
+}
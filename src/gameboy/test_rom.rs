@@ -0,0 +1,217 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use super::GameBoy;
+use crate::emulator::Emulator;
+
+// Instructions run before a ROM counts as timed out. The DMG runs about 0.5M instructions a
+// second, so this is roughly two minutes of emulated time, longer than any Blargg or Mooneye
+// ROM needs on real hardware.
+pub const DEFAULT_MAX_TICKS: u64 = 60_000_000;
+
+// How often the serial output and the 0xA000 signature are polled
+const POLL_INTERVAL: u64 = 4096;
+
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const MOONEYE_FAIL: [u8; 6] = [0x42; 6];
+
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_RUNNING: u8 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestRomKind {
+    // Reports through the serial port and/or the 0xA000 signature
+    Blargg,
+    // Reports through the registers when it hits LD B,B
+    Mooneye,
+}
+
+impl TestRomKind {
+    // Mooneye's test suite lives under `acceptance/`, everything else is treated as Blargg
+    pub fn from_path(path: &Path) -> Self {
+        let path = path.to_string_lossy().to_lowercase();
+        if path.contains("mooneye") || path.contains("acceptance") {
+            TestRomKind::Mooneye
+        } else {
+            TestRomKind::Blargg
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    Failed(String),
+    TimedOut,
+}
+
+impl fmt::Display for TestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestOutcome::Passed => write!(f, "PASS"),
+            TestOutcome::Failed(reason) if reason.is_empty() => write!(f, "FAIL"),
+            TestOutcome::Failed(reason) => write!(f, "FAIL ({})", reason),
+            TestOutcome::TimedOut => write!(f, "TIMEOUT"),
+        }
+    }
+}
+
+pub struct TestRomResult {
+    pub name: String,
+    pub kind: TestRomKind,
+    pub outcome: TestOutcome,
+}
+
+pub fn run_test_rom(rom: &[u8], kind: TestRomKind, max_ticks: u64) -> TestOutcome {
+    let mut gameboy = GameBoy::from_rom(rom);
+
+    for ticks in 0..max_ticks {
+        gameboy.tick();
+
+        match kind {
+            TestRomKind::Mooneye => {
+                if gameboy.take_software_breakpoint() {
+                    return mooneye_outcome(&gameboy);
+                }
+            }
+            TestRomKind::Blargg => {
+                if ticks % POLL_INTERVAL == 0
+                    && let Some(outcome) = blargg_outcome(&gameboy)
+                {
+                    return outcome;
+                }
+            }
+        }
+    }
+
+    match kind {
+        TestRomKind::Blargg => blargg_outcome(&gameboy).unwrap_or(TestOutcome::TimedOut),
+        TestRomKind::Mooneye => TestOutcome::TimedOut,
+    }
+}
+
+pub fn run_test_rom_file(path: &Path, max_ticks: u64) -> Result<TestRomResult> {
+    let rom = fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let kind = TestRomKind::from_path(path);
+
+    Ok(TestRomResult {
+        name: path.display().to_string(),
+        kind,
+        outcome: run_test_rom(&rom, kind, max_ticks),
+    })
+}
+
+// Every `.gb` file below `dir`, sorted so the table is stable between runs
+pub fn find_test_roms(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in
+            fs::read_dir(&dir).with_context(|| format!("Could not read {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "gb") {
+                roms.push(path);
+            }
+        }
+    }
+
+    roms.sort();
+    Ok(roms)
+}
+
+pub fn format_table(results: &[TestRomResult]) -> String {
+    let name_width = results
+        .iter()
+        .map(|result| result.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("ROM".len());
+
+    let mut table = format!("{:<name_width$}  {:<7}  RESULT\n", "ROM", "SUITE");
+    for result in results {
+        let suite = match result.kind {
+            TestRomKind::Blargg => "blargg",
+            TestRomKind::Mooneye => "mooneye",
+        };
+        table += &format!(
+            "{:<name_width$}  {:<7}  {}\n",
+            result.name, suite, result.outcome
+        );
+    }
+
+    let passed = results
+        .iter()
+        .filter(|result| result.outcome == TestOutcome::Passed)
+        .count();
+    table += &format!("{}/{} passed\n", passed, results.len());
+
+    table
+}
+
+fn mooneye_outcome(gameboy: &GameBoy) -> TestOutcome {
+    let regs = [
+        gameboy.b(),
+        gameboy.c(),
+        gameboy.d(),
+        gameboy.e(),
+        gameboy.h(),
+        gameboy.l(),
+    ];
+
+    if regs == MOONEYE_PASS {
+        TestOutcome::Passed
+    } else if regs == MOONEYE_FAIL {
+        TestOutcome::Failed(String::new())
+    } else {
+        TestOutcome::Failed(format!("unexpected registers {:02X?}", regs))
+    }
+}
+
+fn blargg_outcome(gameboy: &GameBoy) -> Option<TestOutcome> {
+    let serial = String::from_utf8_lossy(gameboy.serial_output());
+    if serial.contains("Passed") {
+        return Some(TestOutcome::Passed);
+    }
+    if serial.contains("Failed") {
+        return Some(TestOutcome::Failed(
+            serial.trim().lines().last()?.to_string(),
+        ));
+    }
+
+    let signature = [
+        gameboy.read(0xA001),
+        gameboy.read(0xA002),
+        gameboy.read(0xA003),
+    ];
+    if signature != BLARGG_SIGNATURE {
+        return None;
+    }
+
+    match gameboy.read(0xA000) {
+        BLARGG_RUNNING => None,
+        0 => Some(TestOutcome::Passed),
+        code => Some(TestOutcome::Failed(format!(
+            "code {}: {}",
+            code,
+            blargg_text(gameboy).trim()
+        ))),
+    }
+}
+
+// Zero terminated text the test writes from 0xA004
+fn blargg_text(gameboy: &GameBoy) -> String {
+    let text: Vec<u8> = (0xA004u16..0xC000)
+        .map(|addr| gameboy.read(addr))
+        .take_while(|&byte| byte != 0)
+        .collect();
+
+    String::from_utf8_lossy(&text).into_owned()
+}
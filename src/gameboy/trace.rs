@@ -0,0 +1,30 @@
+use super::GameBoy;
+
+// Log target of the execution trace, enabled at trace level with `--trace` or
+// `RUST_LOG=emulators::gameboy::trace=trace`
+pub const TRACE_TARGET: &str = "emulators::gameboy::trace";
+
+// One line per instruction with the registers before it runs and the four bytes at PC, in the
+// Gameboy Doctor format so traces can be diffed against its reference logs
+//
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+pub fn doctor_line(gameboy: &GameBoy) -> String {
+    let pc = gameboy.pc;
+    let pcmem: Vec<String> = (0..4)
+        .map(|offset| format!("{:02X}", gameboy.read(pc.wrapping_add(offset))))
+        .collect();
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{}",
+        gameboy.a,
+        gameboy.f,
+        gameboy.b(),
+        gameboy.c(),
+        gameboy.d(),
+        gameboy.e(),
+        gameboy.h(),
+        gameboy.l(),
+        gameboy.sp,
+        pc,
+        pcmem.join(",")
+    )
+}
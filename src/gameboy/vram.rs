@@ -0,0 +1,167 @@
+use std::fmt;
+
+use super::GameBoy;
+
+// Tiles in VRAM's three blocks of tile data from 0x8000
+pub const TILE_COUNT: usize = 384;
+// Tilemaps are 32x32 tiles, at 0x9800 and 0x9C00
+pub const TILEMAP_SIZE: usize = 32;
+pub const TILEMAP_ADDRS: [u16; 2] = [0x9800, 0x9C00];
+pub const SPRITE_COUNT: usize = 40;
+
+const TILE_DATA: u16 = 0x8000;
+const OAM: u16 = 0xFE00;
+
+const LCDC: u16 = 0xFF40;
+const SCY: u16 = 0xFF42;
+const SCX: u16 = 0xFF43;
+const BGP: u16 = 0xFF47;
+const OBP0: u16 = 0xFF48;
+const OBP1: u16 = 0xFF49;
+
+// An OAM entry, with X and Y as stored, 8 and 16 pixels right and below the screen position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub index: usize,
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub flags: u8,
+}
+
+impl Sprite {
+    pub fn screen_x(&self) -> i16 {
+        self.x as i16 - 8
+    }
+    pub fn screen_y(&self) -> i16 {
+        self.y as i16 - 16
+    }
+    // Drawn under the background's colors 1-3
+    pub fn behind_background(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+    pub fn flip_y(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+    pub fn flip_x(&self) -> bool {
+        self.flags & 0x20 != 0
+    }
+    // OBP1 rather than OBP0
+    pub fn palette(&self) -> u8 {
+        (self.flags >> 4) & 1
+    }
+}
+
+// 00 X=-008 Y=-016 T=00 OBP0 ---
+impl fmt::Display for Sprite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02} X={:+04} Y={:+04} T={:02X} OBP{} {}{}{}",
+            self.index,
+            self.screen_x(),
+            self.screen_y(),
+            self.tile,
+            self.palette(),
+            if self.flip_x() { 'X' } else { '-' },
+            if self.flip_y() { 'Y' } else { '-' },
+            if self.behind_background() { 'B' } else { '-' },
+        )
+    }
+}
+
+// Everything the VRAM viewers show, decoded from memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VramSnapshot {
+    // Color numbers 0-3 of each tile's 8x8 pixels, row by row
+    pub tiles: Vec<[u8; 64]>,
+    // Index into `tiles` of each tile of the two maps, with LCDC's addressing mode applied
+    pub tilemaps: [Vec<u16>; 2],
+    pub lcdc: u8,
+    pub scx: u8,
+    pub scy: u8,
+    pub bgp: u8,
+    pub obp: [u8; 2],
+    // RGB of the DMG's four shades
+    pub shades: [[u8; 3]; 4],
+    pub sprites: Vec<Sprite>,
+}
+
+impl VramSnapshot {
+    // The map the background is drawn from, 0 or 1
+    pub fn background_map(&self) -> usize {
+        ((self.lcdc >> 3) & 1) as usize
+    }
+
+    // The map the window is drawn from, 0 or 1
+    pub fn window_map(&self) -> usize {
+        ((self.lcdc >> 6) & 1) as usize
+    }
+
+    // RGB of a color number through a palette register such as BGP
+    pub fn color(&self, palette: u8, color: u8) -> [u8; 3] {
+        self.shades[((palette >> (color * 2)) & 0b11) as usize]
+    }
+}
+
+impl GameBoy {
+    pub fn vram_snapshot(&self) -> VramSnapshot {
+        let lcdc = self.read(LCDC);
+        VramSnapshot {
+            tiles: (0..TILE_COUNT).map(|index| self.tile(index)).collect(),
+            tilemaps: [0, 1].map(|map| self.tilemap(map, lcdc)),
+            lcdc,
+            scx: self.read(SCX),
+            scy: self.read(SCY),
+            bgp: self.read(BGP),
+            obp: [self.read(OBP0), self.read(OBP1)],
+            shades: self.palette,
+            sprites: self.sprites(),
+        }
+    }
+
+    // Color numbers of tile `index`, two bitplanes per row with the leftmost pixel in bit 7
+    pub fn tile(&self, index: usize) -> [u8; 64] {
+        let base = TILE_DATA + index as u16 * 16;
+        let mut pixels = [0; 64];
+        for row in 0..8 {
+            let low = self.read(base + row * 2);
+            let high = self.read(base + row * 2 + 1);
+            for column in 0..8 {
+                let bit = 7 - column;
+                pixels[(row * 8 + column) as usize] = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+            }
+        }
+        pixels
+    }
+
+    pub fn sprites(&self) -> Vec<Sprite> {
+        (0..SPRITE_COUNT)
+            .map(|index| {
+                let addr = OAM + index as u16 * 4;
+                Sprite {
+                    index,
+                    y: self.read(addr),
+                    x: self.read(addr + 1),
+                    tile: self.read(addr + 2),
+                    flags: self.read(addr + 3),
+                }
+            })
+            .collect()
+    }
+
+    // With LCDC bit 4 clear, tile numbers are signed offsets from tile 256 (0x9000)
+    fn tilemap(&self, map: usize, lcdc: u8) -> Vec<u16> {
+        let unsigned = lcdc & 0x10 != 0;
+        (0..TILEMAP_SIZE * TILEMAP_SIZE)
+            .map(|offset| {
+                let number = self.read(TILEMAP_ADDRS[map] + offset as u16);
+                if unsigned {
+                    number as u16
+                } else {
+                    (256 + number as i8 as i16) as u16
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    debug::{DebugTarget, register_bytes},
+    scheduler::Scheduler,
+};
+
+// Largest packet GDB may send us, in bytes
+const PACKET_SIZE: usize = 0x1000;
+
+// How many instructions `continue` runs between checks for a Ctrl-C from GDB
+const INTERRUPT_POLL: u64 = 0x1000;
+
+// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// How a GDB session ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ended {
+    Detached,
+    Killed,
+    HungUp,
+}
+
+// A GDB remote serial protocol stub driving one core. Registers and memory are read and written
+// through `DebugTarget`, and the core runs on its own `Scheduler` only while GDB asks it to,
+// so it stops dead between commands. Software breakpoints are kept here rather than patched
+// into memory, so they work in ROM too.
+pub struct GdbServer {
+    target: Box<dyn DebugTarget>,
+    scheduler: Scheduler,
+    breakpoints: BTreeSet<u16>,
+}
+
+impl GdbServer {
+    pub fn new(target: impl DebugTarget + 'static) -> Self {
+        Self {
+            scheduler: Scheduler::new(target.clock()),
+            target: Box::new(target),
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn target(&self) -> &dyn DebugTarget {
+        self.target.as_ref()
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    // Serves GDB on `stream` until it detaches, kills the target or hangs up. Breakpoints are
+    // kept for the next connection.
+    pub fn serve(&mut self, stream: TcpStream) -> Result<Ended> {
+        // Acks and replies are tiny writes that would otherwise wait on GDB's delayed acks
+        stream.set_nodelay(true)?;
+        let mut connection = Connection::new(stream);
+        loop {
+            let packet = match connection.read()? {
+                Some(Incoming::Packet(packet)) => packet,
+                // Ctrl-C while already stopped
+                Some(Incoming::Interrupt) => {
+                    connection.send(&stop_reply(SIGINT))?;
+                    continue;
+                }
+                None => return Ok(Ended::HungUp),
+            };
+            match self.handle(&packet, &mut connection)? {
+                Ok(reply) => connection.send(&reply)?,
+                Err(ended) => return Ok(ended),
+            }
+        }
+    }
+
+    // The reply to `packet`, or how the session ended. Unsupported packets get the empty reply,
+    // as the protocol asks.
+    fn handle(
+        &mut self,
+        packet: &str,
+        connection: &mut Connection,
+    ) -> Result<std::result::Result<String, Ended>> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => stop_reply(SIGTRAP),
+            "g" => {
+                let big_endian = self.target.big_endian();
+                let bytes: Vec<u8> = self
+                    .target
+                    .register_file()
+                    .iter()
+                    .flat_map(|register| register_bytes(register, big_endian))
+                    .collect();
+                to_hex(&bytes)
+            }
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "s" | "c" => {
+                if !args.is_empty() {
+                    let Some(addr) = parse_addr(args) else {
+                        return Ok(Ok(error_reply()));
+                    };
+                    let pc = self.pc_index();
+                    if !self.target.set_register(pc, addr) {
+                        return Ok(Ok(error_reply()));
+                    }
+                }
+                let signal = match command {
+                    "s" => {
+                        self.step();
+                        SIGTRAP
+                    }
+                    _ => self.resume(connection)?,
+                };
+                stop_reply(signal)
+            }
+            "Z" | "z" => self.breakpoint(command == "Z", args),
+            "H" => "OK".to_string(),
+            "D" => {
+                connection.send("OK")?;
+                return Ok(Err(Ended::Detached));
+            }
+            "k" => return Ok(Err(Ended::Killed)),
+            "q" | "Q" => self.query(packet, connection),
+            _ => String::new(),
+        };
+        Ok(Ok(reply))
+    }
+
+    fn query(&mut self, packet: &str, connection: &mut Connection) -> String {
+        if packet.starts_with("qSupported") {
+            return format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+",
+                PACKET_SIZE
+            );
+        }
+        if packet == "QStartNoAckMode" {
+            // The OK is still acknowledged, acks stop after it
+            connection.no_ack_after_reply = true;
+            return "OK".to_string();
+        }
+        if packet == "qAttached" {
+            return "1".to_string();
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = parse_range(range) else {
+                return error_reply();
+            };
+            let xml = target_xml(self.target.as_ref());
+            let start = (offset as usize).min(xml.len());
+            let end = start.saturating_add(length).min(xml.len());
+            // `l` marks the last chunk, `m` that there is more
+            let marker = if end == xml.len() { 'l' } else { 'm' };
+            return format!("{}{}", marker, &xml[start..end]);
+        }
+        String::new()
+    }
+
+    fn pc_index(&self) -> usize {
+        self.target
+            .register_file()
+            .iter()
+            .position(|register| register.name == "pc")
+            .unwrap_or(0)
+    }
+
+    fn step(&mut self) {
+        self.scheduler.step(self.target.as_mut());
+    }
+
+    // Runs until a breakpoint or a Ctrl-C from GDB. The instruction at a breakpoint GDB
+    // continues from is run rather than stopped at again.
+    fn resume(&mut self, connection: &mut Connection) -> Result<u8> {
+        let mut count = 0u64;
+        loop {
+            self.step();
+            if self.breakpoints.contains(&self.target.pc()) {
+                return Ok(SIGTRAP);
+            }
+            count += 1;
+            if count.is_multiple_of(INTERRUPT_POLL) && connection.poll_interrupt()? {
+                return Ok(SIGINT);
+            }
+        }
+    }
+
+    // Z0/z0 software and Z1/z1 hardware breakpoints, which are the same thing here
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut fields = args.split(',');
+        let (Some(kind), Some(addr)) = (fields.next(), fields.next()) else {
+            return error_reply();
+        };
+        if kind != "0" && kind != "1" {
+            return String::new();
+        }
+        let Some(addr) = parse_addr(addr) else {
+            return error_reply();
+        };
+        if insert {
+            self.breakpoints.insert(addr);
+        } else {
+            self.breakpoints.remove(&addr);
+        }
+        "OK".to_string()
+    }
+
+    fn read_register(&self, args: &str) -> String {
+        let register = usize::from_str_radix(args, 16)
+            .ok()
+            .and_then(|index| self.target.register_file().get(index).copied());
+        match register {
+            Some(register) => to_hex(&register_bytes(&register, self.target.big_endian())),
+            None => error_reply(),
+        }
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let Some((index, value)) = args.split_once('=') else {
+            return error_reply();
+        };
+        let (Ok(index), Some(bytes)) = (usize::from_str_radix(index, 16), from_hex(value)) else {
+            return error_reply();
+        };
+        match self.register_value(&bytes) {
+            Some(value) if self.target.set_register(index, value) => "OK".to_string(),
+            _ => error_reply(),
+        }
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        let Some(bytes) = from_hex(args) else {
+            return error_reply();
+        };
+        let mut offset = 0;
+        for (index, register) in self.target.register_file().iter().enumerate() {
+            let width = register.bits as usize / 8;
+            let Some(value) = bytes
+                .get(offset..offset + width)
+                .and_then(|bytes| self.register_value(bytes))
+            else {
+                break;
+            };
+            self.target.set_register(index, value);
+            offset += width;
+        }
+        "OK".to_string()
+    }
+
+    // Bytes from GDB in the target's byte order
+    fn register_value(&self, bytes: &[u8]) -> Option<u16> {
+        match *bytes {
+            [value] => Some(value as u16),
+            [first, second] if self.target.big_endian() => {
+                Some(u16::from_be_bytes([first, second]))
+            }
+            [first, second] => Some(u16::from_le_bytes([first, second])),
+            _ => None,
+        }
+    }
+
+    // Stops at the first unmapped byte, which is only an error if it is the first one
+    fn read_memory(&self, args: &str) -> String {
+        let Some((addr, length)) = parse_range(args) else {
+            return error_reply();
+        };
+        let start = u32::from(addr);
+        let bytes: Vec<u8> = (start..start + length.min(PACKET_SIZE / 2) as u32)
+            .map_while(|addr| {
+                u16::try_from(addr)
+                    .ok()
+                    .and_then(|addr| self.target.read_memory(addr))
+            })
+            .collect();
+        if bytes.is_empty() && length > 0 {
+            return error_reply();
+        }
+        to_hex(&bytes)
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((range, data)) = args.split_once(':') else {
+            return error_reply();
+        };
+        let (Some((addr, length)), Some(bytes)) = (parse_range(range), from_hex(data)) else {
+            return error_reply();
+        };
+        if bytes.len() != length {
+            return error_reply();
+        }
+        for (offset, value) in bytes.into_iter().enumerate() {
+            let written = u16::try_from(usize::from(addr) + offset)
+                .is_ok_and(|addr| self.target.write_memory(addr, value));
+            if !written {
+                return error_reply();
+            }
+        }
+        "OK".to_string()
+    }
+}
+
+// GDB numbers registers in the order they are listed here
+pub fn target_xml(target: &dyn DebugTarget) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n<target version=\"1.0\">\n<feature name=\"org.emulators.cpu\">\n",
+    );
+    for register in target.register_file() {
+        let kind = match register.name {
+            "pc" => "code_ptr",
+            "sp" if register.bits == 16 => "data_ptr",
+            _ => "int",
+        };
+        let _ = writeln!(
+            xml,
+            "<reg name=\"{}\" bitsize=\"{}\" type=\"{}\"/>",
+            register.name, register.bits, kind
+        );
+    }
+    xml.push_str("</feature>\n</target>\n");
+    xml
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+// EFAULT, what gdbserver answers for bad addresses
+fn error_reply() -> String {
+    "E0e".to_string()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_addr(text: &str) -> Option<u16> {
+    u16::from_str_radix(text, 16).ok()
+}
+
+// `addr,length` in hex. Addresses past 0xFFFF don't exist on either core.
+fn parse_range(text: &str) -> Option<(u16, usize)> {
+    let (addr, length) = text.split_once(',')?;
+    Some((parse_addr(addr)?, usize::from_str_radix(length, 16).ok()?))
+}
+
+enum Incoming {
+    Packet(String),
+    // A bare 0x03 byte
+    Interrupt,
+}
+
+// Packet framing: `$data#checksum`, each acknowledged with `+` (or `-` to ask for it again)
+// until GDB switches acks off
+struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    last_sent: Vec<u8>,
+    no_ack: bool,
+    no_ack_after_reply: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            last_sent: Vec::new(),
+            no_ack: false,
+            no_ack_after_reply: false,
+        }
+    }
+
+    // The next packet or interrupt, or None when GDB hung up
+    fn read(&mut self) -> Result<Option<Incoming>> {
+        loop {
+            if let Some(incoming) = self.take_incoming()? {
+                return Ok(Some(incoming));
+            }
+            let mut chunk = [0; 1024];
+            let count = match self.stream.read(&mut chunk) {
+                Ok(count) => count,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) if error.kind() == ErrorKind::ConnectionReset => return Ok(None),
+                Err(error) => return Err(error).context("Could not read from GDB"),
+            };
+            if count == 0 {
+                return Ok(None);
+            }
+            self.buffer.extend_from_slice(&chunk[..count]);
+        }
+    }
+
+    fn take_incoming(&mut self) -> Result<Option<Incoming>> {
+        while let Some(&byte) = self.buffer.first() {
+            match byte {
+                b'$' => {
+                    let Some(end) = self.buffer.iter().position(|&byte| byte == b'#') else {
+                        return Ok(None);
+                    };
+                    if self.buffer.len() < end + 3 {
+                        return Ok(None);
+                    }
+                    let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+                    let data = &packet[1..end];
+                    let checksum = std::str::from_utf8(&packet[end + 1..])
+                        .ok()
+                        .and_then(|text| u8::from_str_radix(text, 16).ok());
+                    if checksum != Some(checksum_of(data)) && !self.no_ack {
+                        self.stream.write_all(b"-")?;
+                        continue;
+                    }
+                    if !self.no_ack {
+                        self.stream.write_all(b"+")?;
+                    }
+                    if data.len() > PACKET_SIZE {
+                        bail!("GDB sent a packet of {} bytes", data.len());
+                    }
+                    return Ok(Some(Incoming::Packet(
+                        String::from_utf8_lossy(data).into_owned(),
+                    )));
+                }
+                0x03 => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Incoming::Interrupt));
+                }
+                b'-' => {
+                    self.buffer.remove(0);
+                    let last_sent = self.last_sent.clone();
+                    self.stream.write_all(&last_sent)?;
+                }
+                // Acks and noise between packets
+                _ => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // Whether GDB sent a Ctrl-C, without waiting for one
+    fn poll_interrupt(&mut self) -> Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut chunk = [0; 1024];
+        let result = self.stream.read(&mut chunk);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            // Hung up, stop so the next read sees it
+            Ok(0) => return Ok(true),
+            Ok(count) => self.buffer.extend_from_slice(&chunk[..count]),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {}
+            Err(error) => return Err(error).context("Could not read from GDB"),
+        }
+        match self.buffer.iter().position(|&byte| byte == 0x03) {
+            Some(index) => {
+                self.buffer.remove(index);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn send(&mut self, data: &str) -> Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream
+            .write_all(packet.as_bytes())
+            .context("Could not write to GDB")?;
+        self.last_sent = packet.into_bytes();
+        if self.no_ack_after_reply {
+            self.no_ack = true;
+            self.no_ack_after_reply = false;
+        }
+        Ok(())
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
@@ -0,0 +1,207 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use image::{Rgba, RgbaImage};
+
+use crate::{emulator::EmulatorDevice, recording::Recorder, scheduler::Scheduler};
+
+// Set this to regenerate golden images instead of comparing against them
+pub const UPDATE_GOLDENS_ENV: &str = "UPDATE_GOLDENS";
+
+// A press or release of one of `Emulator::buttons` applied at the start of `frame`
+#[derive(Clone, Copy, Debug)]
+pub struct ScriptedInput {
+    pub frame: u64,
+    pub button: usize,
+    pub is_pressed: bool,
+}
+
+impl ScriptedInput {
+    pub fn press(frame: u64, button: usize) -> Self {
+        Self {
+            frame,
+            button,
+            is_pressed: true,
+        }
+    }
+
+    pub fn release(frame: u64, button: usize) -> Self {
+        Self {
+            frame,
+            button,
+            is_pressed: false,
+        }
+    }
+}
+
+// Runs a core without a window on the same scheduler as the frontend
+pub struct HeadlessRunner {
+    emulator: EmulatorDevice,
+    scheduler: Scheduler,
+    inputs: Vec<ScriptedInput>,
+    frame: u64,
+    recorder: Option<Recorder>,
+    audio: Vec<f32>,
+}
+
+impl HeadlessRunner {
+    pub fn new(emulator: EmulatorDevice) -> Self {
+        Self {
+            scheduler: Scheduler::new(emulator.device.clock()),
+            emulator,
+            inputs: Vec::new(),
+            frame: 0,
+            recorder: None,
+            audio: Vec::new(),
+        }
+    }
+
+    pub fn with_inputs(mut self, mut inputs: Vec<ScriptedInput>) -> Self {
+        inputs.sort_by_key(|input| input.frame);
+        self.inputs = inputs;
+        self
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn run_frames(&mut self, frames: u64) {
+        for _ in 0..frames {
+            for input in self.inputs.iter().filter(|input| input.frame == self.frame) {
+                self.emulator
+                    .device
+                    .set_button(input.button, input.is_pressed);
+            }
+
+            self.scheduler.run_frame(self.emulator.device.as_mut());
+            self.frame += 1;
+
+            self.audio.clear();
+            self.emulator.device.drain_audio(&mut self.audio);
+            if let Some(recorder) = &mut self.recorder {
+                recorder.push_frame(self.emulator.device.framebuffer());
+                recorder.push_audio(&self.audio);
+            }
+        }
+    }
+
+    // Records every frame run from now on, see `Recorder` for the formats
+    pub fn start_recording(&mut self, path: &Path) -> Result<()> {
+        self.recorder = Some(Recorder::create(path, self.scheduler.clock().frame_rate())?);
+        Ok(())
+    }
+
+    pub fn finish_recording(&mut self) -> Result<Option<PathBuf>> {
+        self.recorder.take().map(Recorder::finish).transpose()
+    }
+
+    pub fn frame_image(&self) -> RgbaImage {
+        let frame = self.emulator.device.framebuffer();
+
+        RgbaImage::from_raw(frame.width as u32, frame.height as u32, frame.data.to_vec())
+            .expect("Framebuffer is smaller than its declared size")
+    }
+}
+
+// FNV-1a over the RGBA bytes of a frame
+pub fn frame_hash(image: &RgbaImage) -> u64 {
+    fnv1a(image.as_raw())
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Renders a frame as text, '#' for anything but the most common color, for readable test
+// failures
+pub fn frame_ascii(image: &RgbaImage) -> String {
+    let mut counts: Vec<(Rgba<u8>, usize)> = Vec::new();
+    for pixel in image.pixels() {
+        match counts.iter_mut().find(|(color, _)| color == pixel) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*pixel, 1)),
+        }
+    }
+    let background = counts
+        .iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(color, _)| color)
+        .unwrap_or(&Rgba([0, 0, 0, 255]));
+
+    let mut text = String::new();
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            text.push(if image.get_pixel(x, y) == background {
+                '.'
+            } else {
+                '#'
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// Compares `image` against the PNG at `path`, or overwrites it when UPDATE_GOLDENS is set.
+// On a mismatch the actual frame is written next to the golden as `<name>.actual.png`.
+pub fn check_golden(image: &RgbaImage, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+
+    if std::env::var_os(UPDATE_GOLDENS_ENV).is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        image
+            .save(path)
+            .with_context(|| format!("Could not write golden {}", path.display()))?;
+        return Ok(());
+    }
+
+    let golden = image::open(path)
+        .with_context(|| {
+            format!(
+                "Could not read golden {} (run with {}=1 to create it)",
+                path.display(),
+                UPDATE_GOLDENS_ENV
+            )
+        })?
+        .to_rgba8();
+
+    let mismatched = if golden.dimensions() != image.dimensions() {
+        None
+    } else {
+        Some(
+            golden
+                .pixels()
+                .zip(image.pixels())
+                .filter(|(expected, actual)| expected != actual)
+                .count(),
+        )
+    };
+
+    if mismatched == Some(0) {
+        return Ok(());
+    }
+
+    let actual_path = path.with_extension("actual.png");
+    image.save(&actual_path)?;
+
+    match mismatched {
+        Some(count) => bail!(
+            "{} differs from golden {} in {} pixels",
+            actual_path.display(),
+            path.display(),
+            count
+        ),
+        None => bail!(
+            "{} is {:?} but golden {} is {:?}",
+            actual_path.display(),
+            image.dimensions(),
+            path.display(),
+            golden.dimensions()
+        ),
+    }
+}
@@ -0,0 +1,236 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, anyhow, bail};
+use winit::keyboard::KeyCode;
+
+use crate::config::{self, Document, Table};
+
+// Layout of the COSMAC VIP's hex keypad on the left of a QWERTY keyboard:
+//   1 2 3 C      1 2 3 4
+//   4 5 6 D  ->  Q W E R
+//   7 8 9 E      A S D F
+//   A 0 B F      Z X C V
+const CHIP8_KEYS: [(&str, KeyCode); 16] = [
+    ("1", KeyCode::Digit1),
+    ("2", KeyCode::Digit2),
+    ("3", KeyCode::Digit3),
+    ("C", KeyCode::Digit4),
+    ("4", KeyCode::KeyQ),
+    ("5", KeyCode::KeyW),
+    ("6", KeyCode::KeyE),
+    ("D", KeyCode::KeyR),
+    ("7", KeyCode::KeyA),
+    ("8", KeyCode::KeyS),
+    ("9", KeyCode::KeyD),
+    ("E", KeyCode::KeyF),
+    ("A", KeyCode::KeyZ),
+    ("0", KeyCode::KeyX),
+    ("B", KeyCode::KeyC),
+    ("F", KeyCode::KeyV),
+];
+
+const GAMEBOY_KEYS: [(&str, KeyCode); 8] = [
+    ("Right", KeyCode::ArrowRight),
+    ("Left", KeyCode::ArrowLeft),
+    ("Up", KeyCode::ArrowUp),
+    ("Down", KeyCode::ArrowDown),
+    ("A", KeyCode::KeyX),
+    ("B", KeyCode::KeyZ),
+    ("Select", KeyCode::Space),
+    ("Start", KeyCode::Enter),
+];
+
+// Keys that can be named in a key bindings file, by their winit names
+#[rustfmt::skip]
+const KEY_CODES: &[KeyCode] = &[
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF,
+    KeyCode::KeyG, KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL,
+    KeyCode::KeyM, KeyCode::KeyN, KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR,
+    KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU, KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX,
+    KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+    KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::NumpadAdd, KeyCode::NumpadSubtract, KeyCode::NumpadMultiply, KeyCode::NumpadDivide,
+    KeyCode::NumpadDecimal, KeyCode::NumpadEnter,
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight,
+    KeyCode::Home, KeyCode::End, KeyCode::PageUp, KeyCode::PageDown, KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Space, KeyCode::Enter, KeyCode::Tab, KeyCode::Escape, KeyCode::Backspace,
+    KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::ControlLeft, KeyCode::ControlRight,
+    KeyCode::AltLeft, KeyCode::AltRight,
+    KeyCode::Backquote, KeyCode::Minus, KeyCode::Equal, KeyCode::BracketLeft,
+    KeyCode::BracketRight, KeyCode::Backslash, KeyCode::Semicolon, KeyCode::Quote,
+    KeyCode::Comma, KeyCode::Period, KeyCode::Slash,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6, KeyCode::F7,
+    KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::F13, KeyCode::F14, KeyCode::F15, KeyCode::F16, KeyCode::F17, KeyCode::F18,
+];
+
+// Keys the frontend handles itself, see `State::handle_key`. `PageUp` and `PageDown` only scroll
+// the debug overlay, but are kept free so they work whenever it is open.
+pub const HOTKEYS: &[KeyCode] = &[
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F12,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::KeyP,
+    KeyCode::KeyN,
+    KeyCode::Tab,
+    KeyCode::Equal,
+    KeyCode::Minus,
+    KeyCode::Backspace,
+];
+
+// Which host keys press which of a core's buttons, see `Emulator::buttons`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Keymap {
+    buttons: &'static [&'static str],
+    // The keys of each button, indexed like `buttons`
+    keys: Vec<Vec<KeyCode>>,
+}
+
+impl Keymap {
+    // Every button unbound
+    pub fn new(buttons: &'static [&'static str]) -> Self {
+        Self {
+            buttons,
+            keys: vec![Vec::new(); buttons.len()],
+        }
+    }
+
+    // The built-in layout for `emulator` ("chip8" or "gameboy"). Buttons it doesn't know about
+    // are left unbound.
+    pub fn defaults(emulator: &str, buttons: &'static [&'static str]) -> Self {
+        let defaults: &[(&str, KeyCode)] = match emulator {
+            "chip8" => &CHIP8_KEYS,
+            "gameboy" => &GAMEBOY_KEYS,
+            _ => &[],
+        };
+
+        let mut keymap = Self::new(buttons);
+        for (name, code) in defaults {
+            if let Some(button) = keymap.position(name) {
+                keymap.keys[button].push(*code);
+            }
+        }
+        keymap
+    }
+
+    pub fn buttons(&self) -> &'static [&'static str] {
+        self.buttons
+    }
+
+    pub fn keys(&self, button: usize) -> &[KeyCode] {
+        self.keys.get(button).map_or(&[], Vec::as_slice)
+    }
+
+    // The button `code` presses, if any
+    pub fn button(&self, code: KeyCode) -> Option<usize> {
+        self.keys.iter().position(|keys| keys.contains(&code))
+    }
+
+    // Button names are matched ignoring case
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.buttons
+            .iter()
+            .position(|button| button.eq_ignore_ascii_case(name))
+    }
+
+    // Replaces the keys of `button`, taking them away from any other button they pressed
+    pub fn bind(&mut self, button: usize, codes: &[KeyCode]) {
+        for keys in &mut self.keys {
+            keys.retain(|code| !codes.contains(code));
+        }
+        if let Some(keys) = self.keys.get_mut(button) {
+            keys.clear();
+            keys.extend_from_slice(codes);
+        }
+    }
+
+    // Applies the `[<emulator>]` section, then the `[<emulator>.rom."<rom>"]` one for the ROM's
+    // file name. Each of their keys is a button name, set to a key name or a list of them.
+    pub fn apply(&mut self, document: &Document, emulator: &str, rom: Option<&str>) -> Result<()> {
+        if let Some(section) = document.section(&[emulator]) {
+            self.apply_table(&section.table)?;
+        }
+        if let Some(section) = rom.and_then(|rom| document.section(&[emulator, "rom", rom])) {
+            self.apply_table(&section.table)?;
+        }
+        Ok(())
+    }
+
+    fn apply_table(&mut self, table: &Table) -> Result<()> {
+        for entry in &table.entries {
+            let button = self.position(&entry.key).ok_or_else(|| {
+                anyhow!(
+                    "line {}: unknown button {}, expected one of {}",
+                    entry.line,
+                    entry.key,
+                    self.buttons.join(", ")
+                )
+            })?;
+            let codes = table
+                .str_list(&entry.key)?
+                .unwrap_or_default()
+                .iter()
+                .map(|name| parse_key(name).with_context(|| format!("line {}", entry.line)))
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(code) = codes.iter().find(|code| HOTKEYS.contains(code)) {
+                bail!(
+                    "line {}: {} is reserved for a hotkey",
+                    entry.line,
+                    key_name(*code)
+                );
+            }
+            self.bind(button, &codes);
+        }
+        Ok(())
+    }
+
+    // Applies a key bindings file on top of this keymap, leaving it unchanged if the file is
+    // invalid
+    pub fn load(&mut self, path: &Path, emulator: &str, rom: Option<&str>) -> Result<()> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        let document = config::parse(&text)
+            .with_context(|| format!("Invalid key bindings file {}", path.display()))?;
+
+        let mut keymap = self.clone();
+        keymap
+            .apply(&document, emulator, rom)
+            .with_context(|| format!("Invalid key bindings file {}", path.display()))?;
+        *self = keymap;
+        Ok(())
+    }
+}
+
+// A winit key name such as `KeyW`, `Digit1` or `ArrowUp`, ignoring case. A single letter or
+// digit is short for its key, so `W` is `KeyW` and `1` is `Digit1`.
+pub fn parse_key(name: &str) -> Result<KeyCode> {
+    let full_name = match name.chars().collect::<Vec<_>>()[..] {
+        [c] if c.is_ascii_alphabetic() => format!("Key{}", c),
+        [c] if c.is_ascii_digit() => format!("Digit{}", c),
+        _ => name.to_string(),
+    };
+    match KEY_CODES
+        .iter()
+        .find(|code| key_name(**code).eq_ignore_ascii_case(&full_name))
+    {
+        Some(code) => Ok(*code),
+        None => bail!(
+            "unknown key {}, expected a name such as KeyW, Digit1 or ArrowUp",
+            name
+        ),
+    }
+}
+
+pub fn key_name(code: KeyCode) -> String {
+    format!("{:?}", code)
+}
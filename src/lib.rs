@@ -1,15 +1,26 @@
 pub mod chip8;
+pub mod config;
+pub mod debug;
+pub mod display;
+pub mod emulation;
 pub mod emulator;
+pub mod frame_buffer;
 pub mod gameboy;
+pub mod gdb;
+pub mod headless;
+pub mod keymap;
+pub mod logging;
+pub mod overlay;
+pub mod palette;
+pub mod postprocess;
+pub mod recording;
+pub mod scheduler;
+pub mod screenshot;
 mod texture;
 
-use std::{
-    sync::{Arc, Mutex},
-    time::Duration,
-};
+use std::{sync::Arc, time::SystemTime};
 
 use image::Rgba;
-use tokio::time;
 use wgpu::util::DeviceExt;
 
 #[cfg(target_arch = "wasm32")]
@@ -19,14 +30,46 @@ use winit::{
     dpi::PhysicalPosition,
     event::*,
     event_loop::{ActiveEventLoop, EventLoop},
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::Window,
 };
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
-use crate::emulator::EmulatorDevice;
+use crate::{
+    debug::DebugSnapshot,
+    display::{DisplaySettings, ScreenFilter, Viewport, fit_viewport},
+    emulation::{EmulationThread, EmulatorCommand, Speed},
+    emulator::EmulatorDevice,
+    logging::STATUS_TARGET,
+    overlay::{OverlayPage, PANEL_COLUMNS, PANEL_ROWS, render_page, render_panel, scroll_memory},
+    postprocess::{PostProcessor, Preset, builtin_presets, find_presets, load_preset},
+};
+
+const WINDOW_TITLE: &str = "Emulator";
+
+// Speeds stepped through with - and =
+const SPEED_STEPS: &[Speed] = &[
+    Speed::Multiplier(0.25),
+    Speed::Multiplier(0.5),
+    Speed::Multiplier(1.0),
+    Speed::Multiplier(2.0),
+    Speed::Multiplier(4.0),
+    Speed::Multiplier(8.0),
+    Speed::Uncapped,
+];
+const NORMAL_SPEED_STEP: usize = 2;
+
+// Frame blending persistence used when it is turned on with F4 but none was given
+const DEFAULT_FRAME_BLEND: f32 = 0.5;
+
+// Largest whole multiple the debug panel is scaled up by
+const MAX_PANEL_SCALE: u32 = 3;
+
+// Most emulated frames the post-processing passes catch up on in one redraw, e.g. when fast
+// forwarding
+const MAX_POSTPROCESS_STEPS: u64 = 4;
 
 // State
 pub struct State {
@@ -38,18 +81,47 @@ pub struct State {
     render_pipeline: wgpu::RenderPipeline,
     window: Arc<Window>,
     vertex_buffer: wgpu::Buffer,
-    num_vertices: u32,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
     diffuse_bind_group: wgpu::BindGroup,
     diffuse_texture: texture::Texture,
-    emulator: Option<Arc<Mutex<EmulatorDevice>>>,
+    display: DisplaySettings,
+    clear_color: wgpu::Color,
+    viewport: Viewport,
+    presets: Vec<Preset>,
+    preset_index: usize,
+    postprocess: PostProcessor,
+    // Persistence F4 turns frame blending back on with
+    frame_blend: f32,
+    // Set when the passes have to run again even without a new frame
+    postprocess_dirty: bool,
+    // `EmulationThread::emulated_frames` when the passes last ran
+    emulated_frames: u64,
+    emulation: Option<EmulationThread>,
+    speed_step: usize,
+    fast_forward: bool,
+    paused: bool,
+    title: String,
+    modifiers: ModifiersState,
+    // Set by Shift+F12, handled once the next frame has been drawn
+    capture_window: bool,
+    // Debug panel drawn to the right of the screen, cycled through its pages with F10
+    debug_page: Option<OverlayPage>,
+    // The latest state shown in the panel, kept to redraw it when the page changes
+    debug_snapshot: Option<DebugSnapshot>,
+    // Start of the panel's hex viewer, scrolled with Page Up and Page Down
+    debug_memory: u16,
+    panel_texture: texture::Texture,
+    panel_bind_group: wgpu::BindGroup,
+    panel_viewport: Viewport,
 }
 
 impl State {
     pub async fn new(
         window: Arc<Window>,
         emulator_device: Option<EmulatorDevice>,
+        display: DisplaySettings,
     ) -> anyhow::Result<Self> {
         let size = window.inner_size();
 
@@ -119,6 +191,7 @@ impl State {
             &device,
             &queue,
             &drawn_image,
+            display.filter.filter_mode(),
             Some("frame_from_thing"),
         )
         .unwrap();
@@ -145,20 +218,28 @@ impl State {
                 ],
                 label: Some("texture_bind_group_layout"),
             });
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
+        let diffuse_bind_group = create_texture_bind_group(
+            &device,
+            &texture_bind_group_layout,
+            &diffuse_texture.view,
+            &diffuse_texture.sampler,
+        );
+
+        let panel = render_panel(&[], PANEL_COLUMNS, PANEL_ROWS);
+        let panel_texture = texture::Texture::new(
+            &device,
+            panel.width(),
+            panel.height(),
+            wgpu::FilterMode::Nearest,
+            Some("debug_panel"),
+        );
+        panel_texture.write(&queue, &panel);
+        let panel_bind_group = create_texture_bind_group(
+            &device,
+            &texture_bind_group_layout,
+            &panel_texture.view,
+            &panel_texture.sampler,
+        );
 
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
@@ -223,75 +304,74 @@ impl State {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let num_vertices = VERTICES.len() as u32;
         let num_indices = INDICES.len() as u32;
 
-        if let Some(emulator_device_found) = emulator_device {
-            let emulator = Arc::new(Mutex::new(emulator_device_found));
+        let clear_color = display::clear_color(display.border_color, config.format.is_srgb());
+        let viewport = fit_viewport(
+            diffuse_texture.size(),
+            (size.width, size.height),
+            display.integer_scaling,
+        );
 
-            let emulator_clone = Arc::clone(&emulator);
-            let emulator_clone_timers = Arc::clone(&emulator);
-
-            // CPU
-            let mut cpu_interval = time::interval(Duration::from_secs_f64(
-                1.0 / emulator.lock().unwrap().cpu_hz,
-            ));
-            tokio::spawn(async move {
-                loop {
-                    {
-                        emulator_clone.lock().unwrap().device.tick();
-                    }
-                    cpu_interval.tick().await;
-                }
-            });
-
-            // Timers
-            let mut timer_interval = time::interval(Duration::from_secs_f64(
-                1.0 / emulator.lock().unwrap().timer_hz,
-            ));
-            tokio::spawn(async move {
-                loop {
-                    {
-                        emulator_clone_timers.lock().unwrap().device.timer_tick();
-                    }
-                    timer_interval.tick().await;
-                }
-            });
-
-            Ok(Self {
-                surface,
-                device,
-                queue,
-                config,
-                is_surface_configured: false,
-                render_pipeline,
-                window,
-                vertex_buffer,
-                num_vertices,
-                index_buffer,
-                num_indices,
-                diffuse_bind_group,
-                diffuse_texture,
-                emulator: Some(emulator),
-            })
-        } else {
-            Ok(Self {
-                surface,
-                device,
-                queue,
-                config,
-                is_surface_configured: false,
-                render_pipeline,
-                window,
-                vertex_buffer,
-                num_vertices,
-                index_buffer,
-                num_indices,
-                diffuse_bind_group,
-                diffuse_texture,
-                emulator: None,
-            })
+        let mut presets = builtin_presets();
+        if let Some(dir) = &display.shader_dir {
+            presets.extend(load_user_presets(dir));
         }
+        let mut preset_index = match &display.shader {
+            Some(name) => presets
+                .iter()
+                .position(|preset| &preset.name == name)
+                .unwrap_or_else(|| {
+                    log::error!("Unknown shader preset {}, using none", name);
+                    0
+                }),
+            None => 0,
+        };
+        let postprocess = PostProcessor::new(&device, &presets[preset_index], display.frame_blend)
+            .or_else(|error| {
+                log::error!("{:#}", error);
+                preset_index = 0;
+                PostProcessor::new(&device, &presets[0], display.frame_blend)
+            })?;
+        let frame_blend = display.frame_blend.unwrap_or(DEFAULT_FRAME_BLEND);
+
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            config,
+            is_surface_configured: false,
+            render_pipeline,
+            window,
+            vertex_buffer,
+            index_buffer,
+            num_indices,
+            texture_bind_group_layout,
+            diffuse_bind_group,
+            diffuse_texture,
+            display,
+            clear_color,
+            viewport,
+            presets,
+            preset_index,
+            postprocess,
+            frame_blend,
+            postprocess_dirty: true,
+            emulated_frames: 0,
+            emulation: emulator_device.map(EmulationThread::spawn),
+            speed_step: NORMAL_SPEED_STEP,
+            fast_forward: false,
+            paused: false,
+            title: WINDOW_TITLE.to_string(),
+            modifiers: ModifiersState::empty(),
+            capture_window: false,
+            debug_page: None,
+            debug_snapshot: None,
+            debug_memory: 0,
+            panel_texture,
+            panel_bind_group,
+            panel_viewport: viewport,
+        })
     }
 
     pub fn resize(&mut self, _width: u32, _height: u32) {
@@ -300,26 +380,248 @@ impl State {
             self.config.height = _height;
             self.surface.configure(&self.device, &self.config);
             self.is_surface_configured = true;
+            self.rebuild_screen();
         }
     }
 
-    pub fn handle_key(&self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
-        {
-            self.emulator
+    // Keeps the screen's aspect ratio, leaving bars of the border color around it, and resizes
+    // the post-processing passes to match
+    fn rebuild_screen(&mut self) {
+        let mut screen_width = self.config.width;
+        if self.debug_page.is_some() {
+            // The panel takes the right of the window, at most half of it
+            let panel_size = self.panel_texture.size();
+            let scale = (self.config.height / panel_size.1).clamp(1, MAX_PANEL_SCALE);
+            let panel_width = (panel_size.0 * scale).min(self.config.width / 2);
+            self.panel_viewport = fit_viewport(panel_size, (panel_width, self.config.height), true);
+            self.panel_viewport.x += (self.config.width - panel_width) as f32;
+            self.panel_viewport.y = 0.0;
+            screen_width = (self.config.width - panel_width).max(1);
+        }
+        self.viewport = fit_viewport(
+            self.diffuse_texture.size(),
+            (screen_width, self.config.height),
+            self.display.integer_scaling,
+        );
+        self.postprocess.resize(
+            &self.device,
+            &self.diffuse_texture.view,
+            self.diffuse_texture.size(),
+            (
+                self.viewport.width.round() as u32,
+                self.viewport.height.round() as u32,
+            ),
+        );
+        self.rebuild_screen_bind_group();
+        self.postprocess_dirty = true;
+    }
+
+    // The final draw reads the last pass's output, or the frame itself without any passes
+    fn rebuild_screen_bind_group(&mut self) {
+        self.diffuse_bind_group = create_texture_bind_group(
+            &self.device,
+            &self.texture_bind_group_layout,
+            self.postprocess
+                .output()
+                .unwrap_or(&self.diffuse_texture.view),
+            &self.diffuse_texture.sampler,
+        );
+    }
+
+    pub fn handle_key(&mut self, _event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
+        if (code, is_pressed) == (KeyCode::F2, true) {
+            self.set_filter(self.display.filter.toggled());
+            return;
+        }
+        if (code, is_pressed) == (KeyCode::F12, true) {
+            if self.modifiers.shift_key() {
+                self.capture_window = true;
+            } else {
+                self.save_frame_screenshot();
+            }
+            return;
+        }
+        if (code, is_pressed) == (KeyCode::F3, true) {
+            self.set_preset((self.preset_index + 1) % self.presets.len());
+            return;
+        }
+        if (code, is_pressed) == (KeyCode::F4, true) {
+            self.set_frame_blend(match self.display.frame_blend {
+                Some(_) => None,
+                None => Some(self.frame_blend),
+            });
+            return;
+        }
+
+        let Some(emulation) = &self.emulation else {
+            return;
+        };
+
+        if (code, is_pressed) == (KeyCode::F5, true) && !self.display.palettes.is_empty() {
+            self.display.palette = (self.display.palette + 1) % self.display.palettes.len();
+            let palette = self.display.palettes[self.display.palette].clone();
+            log::info!("Palette: {}", palette.name);
+            emulation.send(EmulatorCommand::SetPalette(palette));
+            return;
+        }
+        if (code, is_pressed) == (KeyCode::F10, true) {
+            let has_vram = self
+                .debug_snapshot
                 .as_ref()
-                .unwrap()
-                .lock()
-                .unwrap()
-                .device
-                .handle_key(code, is_pressed);
+                .is_some_and(|snapshot| snapshot.vram.is_some());
+            self.debug_page = OverlayPage::next(self.debug_page, has_vram);
+            emulation.send(EmulatorCommand::SetDebugView(
+                self.debug_page.map(|_| self.debug_memory),
+            ));
+            self.update_panel();
+            self.rebuild_screen();
+            return;
         }
+        if self.debug_page.is_some() && is_pressed {
+            let pages = match code {
+                KeyCode::PageUp => -1,
+                KeyCode::PageDown => 1,
+                _ => 0,
+            };
+            if pages != 0 {
+                self.debug_memory = scroll_memory(self.debug_memory, pages);
+                emulation.send(EmulatorCommand::SetDebugView(Some(self.debug_memory)));
+                return;
+            }
+        }
+        if (code, is_pressed) == (KeyCode::F9, true) {
+            if emulation.is_recording() {
+                emulation.send(EmulatorCommand::StopRecording);
+            } else {
+                let path = self.display.screenshot_dir.join(format!(
+                    "recording-{}.{}",
+                    screenshot::timestamp(SystemTime::now()),
+                    self.display.recording_format.extension()
+                ));
+                emulation.send(EmulatorCommand::StartRecording(path));
+            }
+            return;
+        }
+
         match (code, is_pressed) {
-            // (KeyCode::Escape, true) => event_loop.exit(),
-            _ => {}
+            (KeyCode::KeyP, true) => {
+                self.paused = !self.paused;
+                emulation.send(EmulatorCommand::SetPaused(self.paused));
+            }
+            (KeyCode::KeyN, true) => {
+                self.paused = true;
+                emulation.send(EmulatorCommand::AdvanceFrame);
+            }
+            (KeyCode::Tab, _) => {
+                self.fast_forward = is_pressed;
+                emulation.send(EmulatorCommand::SetSpeed(self.speed()));
+            }
+            (KeyCode::Equal, true) => {
+                self.speed_step = (self.speed_step + 1).min(SPEED_STEPS.len() - 1);
+                emulation.send(EmulatorCommand::SetSpeed(self.speed()));
+            }
+            (KeyCode::Minus, true) => {
+                self.speed_step = self.speed_step.saturating_sub(1);
+                emulation.send(EmulatorCommand::SetSpeed(self.speed()));
+            }
+            (KeyCode::Backspace, true) => {
+                self.speed_step = NORMAL_SPEED_STEP;
+                emulation.send(EmulatorCommand::SetSpeed(self.speed()));
+            }
+            (code, false) if keymap::HOTKEYS.contains(&code) => {}
+            _ => {
+                if let Some(button) = self.display.keymap.button(code) {
+                    emulation.set_button(button, is_pressed);
+                }
+            }
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: ScreenFilter) {
+        self.display.filter = filter;
+        self.diffuse_texture
+            .set_filter(&self.device, filter.filter_mode());
+        self.rebuild_screen_bind_group();
+    }
+
+    // Keeps the current preset if the new one fails to build
+    pub fn set_preset(&mut self, index: usize) {
+        match PostProcessor::new(&self.device, &self.presets[index], self.display.frame_blend) {
+            Ok(postprocess) => {
+                self.preset_index = index;
+                self.postprocess = postprocess;
+                self.rebuild_screen();
+                log::info!("Shader preset: {}", self.presets[index].name);
+            }
+            Err(error) => log::error!("{:#}", error),
+        }
+    }
+
+    pub fn set_frame_blend(&mut self, frame_blend: Option<f32>) {
+        self.display.frame_blend = frame_blend;
+        if let Some(persistence) = frame_blend {
+            self.frame_blend = persistence;
+        }
+        self.set_preset(self.preset_index);
+    }
+
+    // The last emulated frame at its native resolution, without any shaders
+    pub fn save_frame_screenshot(&mut self) {
+        let Some(emulation) = &mut self.emulation else {
+            return;
+        };
+
+        let result = screenshot::frame_image(emulation.frames().latest()).and_then(|image| {
+            screenshot::save_timestamped(&image, &self.display.screenshot_dir, "screenshot")
+        });
+        report_screenshot(result);
+    }
+
+    // What is in the window, shaders and border included, read back from the GPU
+    fn save_window_screenshot(&self) {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("window_screenshot"),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Screenshot Encoder"),
+            });
+        self.draw_screen(
+            &mut encoder,
+            &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let result =
+            screenshot::read_texture(&self.device, &self.queue, &texture).and_then(|image| {
+                screenshot::save_timestamped(&image, &self.display.screenshot_dir, "window")
+            });
+        report_screenshot(result);
+    }
+
+    // Holding Tab fast-forwards uncapped, otherwise the speed picked with - and =
+    fn speed(&self) -> Speed {
+        if self.fast_forward {
+            Speed::Uncapped
+        } else {
+            SPEED_STEPS[self.speed_step]
         }
     }
 
-    pub fn handle_mouse_moved(&mut self, device_id: DeviceId, position: PhysicalPosition<f64>) {}
+    pub fn handle_mouse_moved(&mut self, _device_id: DeviceId, _position: PhysicalPosition<f64>) {}
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         self.window.request_redraw();
@@ -340,124 +642,232 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
-        let temp_vbuf: Vec<u8>;
-        let emu_width: usize;
-        let emu_height: usize;
-
-        if self.emulator.is_some() {
-            // TODO: just pass vbuf as a buffer directly, along with width & height
-            {
-                (temp_vbuf, emu_width, emu_height) = self
-                    .emulator
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .device
-                    .get_vbuf()
-                    .clone();
+        let mut new_frame = false;
+        let mut resized = false;
+        if let Some(frame) = self
+            .emulation
+            .as_mut()
+            .and_then(|emulation| emulation.frames().read())
+        {
+            new_frame = true;
+            let size = (frame.width as u32, frame.height as u32);
+            if self.diffuse_texture.size() != size {
+                // Only happens when the core changes resolution
+                self.diffuse_texture = texture::Texture::new(
+                    &self.device,
+                    size.0,
+                    size.1,
+                    self.display.filter.filter_mode(),
+                    Some("emulator_frame"),
+                );
+                resized = true;
             }
 
-            let mut vbuf_image = image::RgbaImage::new(emu_width as u32, emu_height as u32);
+            self.diffuse_texture.write(&self.queue, &frame.data);
+        }
+        if resized {
+            self.rebuild_screen();
+        }
 
-            for (x, y, pixel) in vbuf_image.enumerate_pixels_mut() {
-                let r = temp_vbuf[(x as usize + y as usize * emu_width) * 3];
-                let g = temp_vbuf[(x as usize + y as usize * emu_width) * 3 + 1];
-                let b = temp_vbuf[(x as usize + y as usize * emu_width) * 3 + 2];
+        if let Some(snapshot) = self
+            .emulation
+            .as_ref()
+            .and_then(EmulationThread::take_debug_snapshot)
+        {
+            self.debug_snapshot = Some(snapshot);
+            self.update_panel();
+        }
 
-                *pixel = Rgba([r, g, b, 255]);
-            }
+        // Passes advance once per emulated frame, whether or not the picture changed, so
+        // blending and ghosting fade out erased sprites but hold still while paused
+        let emulated_frames = self
+            .emulation
+            .as_ref()
+            .map_or(0, EmulationThread::emulated_frames);
+        let mut steps = emulated_frames
+            .saturating_sub(self.emulated_frames)
+            .min(MAX_POSTPROCESS_STEPS);
+        self.emulated_frames = emulated_frames;
+        if steps == 0 && (new_frame || self.postprocess_dirty) {
+            steps = 1;
+        }
+        // Each step writes its own uniforms, which only take effect on submit
+        for _ in 1..steps {
+            let mut step_encoder =
+                self.device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Postprocess Step Encoder"),
+                    });
+            self.postprocess.run(&self.queue, &mut step_encoder);
+            self.queue.submit(std::iter::once(step_encoder.finish()));
+        }
+        if steps > 0 {
+            self.postprocess.run(&self.queue, &mut encoder);
+        }
+        self.postprocess_dirty = false;
+
+        self.draw_screen(&mut encoder, &view);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+
+        if std::mem::take(&mut self.capture_window) {
+            self.save_window_screenshot();
+        }
 
-            match texture::Texture::from_image_buffer(
+        Ok(())
+    }
+
+    // Draws the current page of the latest snapshot into the panel's texture. Pages differ in
+    // size, so the texture and the layout change with them.
+    fn update_panel(&mut self) {
+        let (Some(page), Some(snapshot)) = (self.debug_page, &self.debug_snapshot) else {
+            return;
+        };
+        let panel = render_page(snapshot, page);
+        if panel.dimensions() != self.panel_texture.size() {
+            self.panel_texture = texture::Texture::new(
                 &self.device,
-                &self.queue,
-                &vbuf_image,
-                Some("frame_from_thing"),
-            ) {
-                Ok(vbuf_tex) => {
-                    self.diffuse_texture = vbuf_tex;
-                }
-                Err(err) => {
-                    log::error!("Could not create texture: {}", err);
-                }
-            };
+                panel.width(),
+                panel.height(),
+                wgpu::FilterMode::Nearest,
+                Some("debug_panel"),
+            );
+            self.panel_bind_group = create_texture_bind_group(
+                &self.device,
+                &self.texture_bind_group_layout,
+                &self.panel_texture.view,
+                &self.panel_texture.sampler,
+            );
+            self.rebuild_screen();
         }
+        self.panel_texture.write(&self.queue, &panel);
+    }
 
-        let texture_bind_group_layout =
-            self.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                    ],
-                    label: Some("texture_bind_group_layout"),
-                });
-        self.diffuse_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&self.diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&self.diffuse_texture.sampler),
+    // Border plus the screen scaled into the viewport, and the debug panel when it is shown
+    fn draw_screen(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: wgpu::StoreOp::Store,
                 },
-            ],
-            label: Some("diffuse_bind_group"),
+                depth_slice: None, // Look back at this
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
         });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 1.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None, // Look back at this
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-                multiview_mask: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.set_viewport(
+            self.viewport.x,
+            self.viewport.y,
+            self.viewport.width,
+            self.viewport.height,
+            0.0,
+            1.0,
+        );
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+
+        if self.debug_page.is_some() {
+            render_pass.set_viewport(
+                self.panel_viewport.x,
+                self.panel_viewport.y,
+                self.panel_viewport.width,
+                self.panel_viewport.height,
+                0.0,
+                1.0,
+            );
+            render_pass.set_bind_group(0, &self.panel_bind_group, &[]);
             render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
         }
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+    fn update(&mut self) {
+        let Some(emulation) = &self.emulation else {
+            return;
+        };
 
-        Ok(())
+        let mut title = if self.paused {
+            format!("{} - Paused", WINDOW_TITLE)
+        } else {
+            let target = match self.speed() {
+                Speed::Multiplier(multiplier) => format!("{}%", multiplier * 100.0),
+                Speed::Uncapped => "uncapped".to_string(),
+            };
+            format!(
+                "{} - {:.0}% (target {})",
+                WINDOW_TITLE,
+                emulation.measured_speed() * 100.0,
+                target
+            )
+        };
+        if emulation.is_recording() {
+            title.push_str(" - Recording");
+        }
+
+        if title != self.title {
+            self.window.set_title(&title);
+            self.title = title;
+        }
     }
+}
 
-    fn update(&mut self) {}
+fn report_screenshot(result: anyhow::Result<std::path::PathBuf>) {
+    match result {
+        Ok(path) => log::info!(target: STATUS_TARGET, "Saved {}", path.display()),
+        Err(error) => log::error!("Could not save screenshot: {:#}", error),
+    }
+}
+
+fn create_texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("diffuse_bind_group"),
+    })
+}
+
+// Presets that fail to load are reported and skipped
+fn load_user_presets(dir: &std::path::Path) -> Vec<Preset> {
+    let manifests = match find_presets(dir) {
+        Ok(manifests) => manifests,
+        Err(error) => {
+            log::error!("{:#}", error);
+            return Vec::new();
+        }
+    };
+
+    manifests
+        .iter()
+        .filter_map(|manifest| {
+            load_preset(manifest)
+                .map_err(|error| log::error!("{:#}", error))
+                .ok()
+        })
+        .collect()
 }
 
 // App
@@ -466,12 +876,14 @@ pub struct App {
     proxy: Option<winit::event_loop::EventLoopProxy<State>>,
     state: Option<State>,
     emulator_device: Option<EmulatorDevice>,
+    display: DisplaySettings,
 }
 
 impl App {
     pub fn new(
         #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>,
         emulator_device: Option<EmulatorDevice>,
+        display: DisplaySettings,
     ) -> Self {
         #[cfg(target_arch = "wasm32")]
         let proxy = Some(event_loop.create_proxy());
@@ -480,6 +892,7 @@ impl App {
             #[cfg(target_arch = "wasm32")]
             proxy,
             emulator_device,
+            display,
         }
     }
 }
@@ -487,7 +900,7 @@ impl App {
 impl ApplicationHandler<State> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         #[allow(unused_mut)]
-        let mut window_attributes = Window::default_attributes().with_title("Emulator");
+        let mut window_attributes = Window::default_attributes().with_title(WINDOW_TITLE);
 
         #[cfg(target_arch = "wasm32")]
         {
@@ -508,8 +921,14 @@ impl ApplicationHandler<State> for App {
         #[cfg(not(target_arch = "wasm32"))]
         {
             // If we are not on web we can use pollster to await
-            self.state =
-                Some(pollster::block_on(State::new(window, self.emulator_device.take())).unwrap());
+            self.state = Some(
+                pollster::block_on(State::new(
+                    window,
+                    self.emulator_device.take(),
+                    self.display.clone(),
+                ))
+                .unwrap(),
+            );
         }
 
         #[cfg(target_arch = "wasm32")]
@@ -575,10 +994,12 @@ impl ApplicationHandler<State> for App {
                     KeyEvent {
                         physical_key: PhysicalKey::Code(code),
                         state: key_state,
+                        repeat: false,
                         ..
                     },
                 ..
             } => state.handle_key(event_loop, code, key_state.is_pressed()),
+            WindowEvent::ModifiersChanged(modifiers) => state.modifiers = modifiers.state(),
             WindowEvent::CursorMoved {
                 device_id,
                 position,
@@ -658,11 +1079,8 @@ const VERTICES: &[Vertex] = &[
 
 const INDICES: &[u16] = &[2, 1, 3, 3, 1, 0]; //0, 1, 3, 3, 1, 2];
 
-pub fn run(emulator: Option<EmulatorDevice>) -> anyhow::Result<()> {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        env_logger::init();
-    }
+pub fn run(emulator: Option<EmulatorDevice>, display: DisplaySettings) -> anyhow::Result<()> {
+    // Native builds set up logging in main, see `logging::init`
     #[cfg(target_arch = "wasm32")]
     {
         console_log::init_with_level(log::Level::Info).unwrap_throw();
@@ -673,6 +1091,7 @@ pub fn run(emulator: Option<EmulatorDevice>) -> anyhow::Result<()> {
         #[cfg(target_arch = "wasm32")]
         &event_loop,
         emulator,
+        display,
     );
     let _ = event_loop.run_app(&mut app);
     Ok(())
@@ -0,0 +1,86 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::{chip8, gameboy};
+
+// Where screenshots and recordings were saved, shown by default
+pub const STATUS_TARGET: &str = "emulators::status";
+
+// The execution traces of each core
+const TRACE_TARGETS: [&str; 2] = [chip8::trace::TRACE_TARGET, gameboy::trace::TRACE_TARGET];
+
+// env_logger configured from RUST_LOG (errors and STATUS_TARGET by default), with the
+// execution traces turned on by `trace`. With a `trace_file` the trace goes there as bare
+// lines, without env_logger's timestamps, so runs can be diffed; everything else still goes to
+// stderr. If the file can't be created, the error is returned after installing the logger
+// without it, so the trace goes to stderr too.
+pub fn init(trace: bool, trace_file: Option<&Path>) -> Result<()> {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Error);
+    builder.filter_module(STATUS_TARGET, LevelFilter::Info);
+    builder.parse_default_env();
+    if trace || trace_file.is_some() {
+        for target in TRACE_TARGETS {
+            builder.filter_module(target, LevelFilter::Trace);
+        }
+    }
+    let inner = builder.build();
+
+    let file = trace_file.map(|path| {
+        File::create(path).with_context(|| format!("Could not create {}", path.display()))
+    });
+    let (trace_file, file_error) = match file {
+        Some(Ok(file)) => (Some(Mutex::new(BufWriter::new(file))), None),
+        Some(Err(error)) => (None, Some(error)),
+        None => (None, None),
+    };
+
+    log::set_max_level(inner.filter());
+    log::set_boxed_logger(Box::new(Logger { inner, trace_file }))?;
+    match file_error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+struct Logger {
+    inner: env_logger::Logger,
+    trace_file: Option<Mutex<BufWriter<File>>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+
+        match &self.trace_file {
+            Some(file) if TRACE_TARGETS.contains(&record.target()) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(file, "{}", record.args());
+                }
+            }
+            _ => self.inner.log(record),
+        }
+    }
+
+    // The logger is never dropped, so this has to be called before exiting to keep the end of
+    // the trace
+    fn flush(&self) {
+        self.inner.flush();
+        if let Some(Ok(mut file)) = self.trace_file.as_ref().map(Mutex::lock) {
+            let _ = file.flush();
+        }
+    }
+}
@@ -1,7 +1,18 @@
-use clap::Parser;
+use anyhow::Context;
+use clap::{Parser, Subcommand};
 use emulators::chip8::Chip8;
+use emulators::chip8::asm::assemble_as;
+use emulators::chip8::debugger::{Debugger, repl};
+use emulators::chip8::disasm::{Dialect, disassemble_rom_as};
+use emulators::display::{DisplaySettings, ScreenFilter, parse_hex_color};
 use emulators::emulator::EmulatorDevice;
 use emulators::gameboy::GameBoy;
+use emulators::gdb::{Ended, GdbServer};
+use emulators::headless::HeadlessRunner;
+use emulators::keymap::Keymap;
+use emulators::logging::STATUS_TARGET;
+use emulators::palette::{builtin_palettes, load_palettes, select_palette};
+use emulators::recording::RecordingFormat;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -18,27 +29,366 @@ struct Args {
     // Name of the emulator you want to use (i.e. Chip8, NES, Gameboy)
     #[arg(short, long, default_value_t = String::from("Chip8"), long_help="Name of the emulator you want to use (i.e. Chip8, NES, Gameboy)")]
     emulator: String,
+
+    // Only scale the screen by whole multiples
+    #[arg(
+        long,
+        long_help = "Only scale the screen by whole multiples, leaving a border"
+    )]
+    integer_scale: bool,
+
+    // Color of the border around the screen
+    #[arg(long, default_value = "#000000", value_parser = parse_hex_color, long_help = "Color of the border around the screen, as #RRGGBB")]
+    border_color: [u8; 3],
+
+    // How the screen is smoothed when scaled
+    #[arg(long, value_enum, default_value_t = ScreenFilter::Nearest, long_help = "How the screen is smoothed when scaled, toggled at runtime with F2")]
+    filter: ScreenFilter,
+
+    // Post-processing shader preset
+    #[arg(
+        long,
+        long_help = "Post-processing shader preset: none, scanlines, crt, dmg-lcd or one from --shader-dir, cycled at runtime with F3"
+    )]
+    shader: Option<String>,
+
+    // Blend each frame with the previous ones to hide flicker
+    #[arg(long, value_name = "PERSISTENCE", num_args = 0..=1, default_missing_value = "0.5", long_help = "Blend each frame with the previous ones to hide flicker, keeping PERSISTENCE (0 to 1) of the last frame. Toggled at runtime with F4")]
+    frame_blend: Option<f32>,
+
+    // Directory of extra shader presets
+    #[arg(
+        long,
+        long_help = "Directory of extra shader presets, one TOML manifest per preset listing its WGSL passes"
+    )]
+    shader_dir: Option<std::path::PathBuf>,
+
+    // Palette for monochrome cores
+    #[arg(
+        long,
+        long_help = "Palette for monochrome cores: dmg-green, pocket-gray, light, grayscale, classic, one from --palette-file, or 2 or 4 comma separated hex colors. Cycled at runtime with F5"
+    )]
+    palette: Option<String>,
+
+    // File of extra palettes
+    #[arg(
+        long,
+        long_help = "TOML file of extra palettes, each a [[palette]] section with a name and 2 or 4 hex colors"
+    )]
+    palette_file: Option<std::path::PathBuf>,
+
+    // File of key bindings
+    #[arg(
+        long,
+        long_help = "TOML file binding keys to the emulator's buttons, with a [chip8] or [gameboy] section and per-ROM [chip8.rom.\"file name\"] overrides"
+    )]
+    keymap: Option<std::path::PathBuf>,
+
+    // Where screenshots are saved
+    #[arg(
+        long,
+        default_value = ".",
+        long_help = "Directory F12 (frame) and Shift+F12 (window) screenshots are saved to"
+    )]
+    screenshot_dir: std::path::PathBuf,
+
+    // Format of recordings started with F9
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = RecordingFormat::Gif,
+        long_help = "Format of recordings started with F9: an animated GIF, or uncompressed Y4M video with a WAV of the audio next to it"
+    )]
+    record_format: RecordingFormat,
+
+    // Runs without a window and records to this file
+    #[arg(
+        long,
+        long_help = "Run the ROM without a window for --frames frames, recording them to this .gif or .y4m file (plus a .wav for .y4m)"
+    )]
+    record: Option<std::path::PathBuf>,
+
+    // How long a --record run lasts
+    #[arg(
+        long,
+        default_value_t = 600,
+        long_help = "Number of frames emulated by --record"
+    )]
+    frames: u64,
+
+    // Debug a CHIP-8 ROM from the terminal
+    #[arg(
+        long,
+        long_help = "Run a CHIP-8 ROM in an interactive terminal debugger instead of the window. Type help at the prompt for its commands"
+    )]
+    debug: bool,
+
+    // Serve a GDB remote stub instead of opening the window
+    #[arg(
+        long,
+        value_name = "PORT",
+        long_help = "Wait for GDB on 127.0.0.1:PORT (e.g. target remote :1234) and run the ROM only as it commands, without the window. Supports registers, memory, stepping, continuing and breakpoints"
+    )]
+    gdb: Option<u16>,
+
+    // Log every instruction
+    #[arg(
+        long,
+        long_help = "Log every instruction. CHIP-8 lines hold the address, opcode, disassembly and the registers it changed, GameBoy lines use the Gameboy Doctor format. Also enabled by RUST_LOG=emulators::chip8::trace=trace or emulators::gameboy::trace=trace"
+    )]
+    trace: bool,
+
+    // Where the trace goes instead of stderr
+    #[arg(
+        long,
+        long_help = "Write the --trace log to this file as plain lines, one per instruction, for diffing against other runs or emulators. Implies --trace"
+    )]
+    trace_file: Option<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    // Prints a ROM as assembly
+    #[command(
+        about = "Disassemble a CHIP-8 ROM, with labels for jump, call and I targets. The output can be assembled back into the same ROM"
+    )]
+    Disasm {
+        rom: std::path::PathBuf,
+
+        // Where the listing goes instead of stdout
+        #[arg(
+            short,
+            long,
+            long_help = "Write the listing to this file instead of stdout"
+        )]
+        output: Option<std::path::PathBuf>,
+
+        // Which extension instructions to decode
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = Dialect::Chip8,
+            long_help = "Instruction set to decode: plain CHIP-8, SUPER-CHIP or XO-CHIP"
+        )]
+        dialect: Dialect,
+    },
+
+    // Builds a ROM from assembly
+    #[command(
+        about = "Assemble a CHIP-8 program into a ROM. Accepts the disassembler's output plus labels, EQU constants and DB/DW data"
+    )]
+    Asm {
+        source: std::path::PathBuf,
+
+        // The ROM to write
+        #[arg(short, long, long_help = "Where to write the assembled ROM")]
+        output: std::path::PathBuf,
+
+        // Which extension instructions to accept
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = Dialect::Chip8,
+            long_help = "Instruction set to accept: plain CHIP-8, SUPER-CHIP or XO-CHIP"
+        )]
+        dialect: Dialect,
+    },
 }
-#[tokio::main]
-async fn main() {
+fn main() {
     let args = Args::parse();
 
-    // let mut chip8 = Chip8::new(&args.filepath);
-    // chip8.set_colors([136, 192, 112], [8, 24, 32]);
+    if let Err(error) = emulators::logging::init(args.trace, args.trace_file.as_deref()) {
+        eprintln!("{:#}", error);
+    }
+    launch(args);
+    log::logger().flush();
+}
+
+fn launch(args: Args) {
+    if let Some(command) = &args.command {
+        if let Err(error) = run_command(command) {
+            eprintln!("{:#}", error);
+        }
+        return;
+    }
+
+    let rom = match read_rom(&args.filepath) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("{:#}", error);
+            return;
+        }
+    };
+
+    if args.debug {
+        if !args.emulator.eq_ignore_ascii_case("chip8") {
+            eprintln!("--debug only supports the Chip8 emulator");
+            return;
+        }
+        let chip8 = match Chip8::from_rom(&rom) {
+            Ok(chip8) => chip8,
+            Err(error) => {
+                eprintln!("{:#}", error);
+                return;
+            }
+        };
+        let mut debugger = Debugger::new(chip8);
+        if let Err(error) = repl(&mut debugger, std::io::stdin().lock(), std::io::stdout()) {
+            eprintln!("{:#}", error);
+        }
+        return;
+    }
+
+    if let Some(port) = args.gdb {
+        let server = match args.emulator.to_lowercase().as_str() {
+            "chip8" => Chip8::from_rom(&rom).map(GdbServer::new),
+            "gameboy" => Ok(GdbServer::new(GameBoy::from_rom(&rom))),
+            _ => Err(anyhow::anyhow!("Unknown emulator: {}", args.emulator)),
+        };
+        if let Err(error) = server.and_then(|server| serve_gdb(server, port)) {
+            eprintln!("{:#}", error);
+        }
+        return;
+    }
 
-    // let emulator_device = Some(EmulatorDevice {
-    //     device: Box::new(chip8),
-    //     timer_hz: 60.0,
-    //     cpu_hz: 1000.0,
-    // });
+    let mut emulator_device = match args.emulator.to_lowercase().as_str() {
+        "chip8" => match Chip8::from_rom(&rom) {
+            Ok(chip8) => Some(EmulatorDevice::new(chip8)),
+            Err(error) => {
+                eprintln!("{:#}", error);
+                return;
+            }
+        },
+        "gameboy" => Some(EmulatorDevice::new(GameBoy::from_rom(&rom))),
+        _ => {
+            eprintln!("Unknown emulator: {}", args.emulator);
+            None
+        }
+    };
 
-    let gameboy = GameBoy::new();
-    let emulator_device = Some(EmulatorDevice {
-        device: Box::new(gameboy),
-        timer_hz: 60.0,
-        cpu_hz: 4_000_000.0,
-    });
+    let mut palettes = builtin_palettes();
+    if let Some(path) = &args.palette_file {
+        match load_palettes(path) {
+            Ok(loaded) => palettes.extend(loaded),
+            Err(error) => eprintln!("{:#}", error),
+        }
+    }
+    let palette = match &args.palette {
+        Some(spec) => select_palette(&mut palettes, spec).unwrap_or_else(|error| {
+            eprintln!("{:#}", error);
+            0
+        }),
+        None => 0,
+    };
 
-    let _ = emulators::run(emulator_device);
+    if let Some(emulator) = &mut emulator_device {
+        emulator.device.set_palette(&palettes[palette]);
+    }
+
+    let emulator_name = args.emulator.to_lowercase();
+    let buttons = emulator_device
+        .as_ref()
+        .map_or(&[][..], |emulator| emulator.device.buttons());
+    let mut keymap = Keymap::defaults(&emulator_name, buttons);
+    if let Some(path) = &args.keymap {
+        let rom = std::path::Path::new(&args.filepath)
+            .file_name()
+            .and_then(|name| name.to_str());
+        if let Err(error) = keymap.load(path, &emulator_name, rom) {
+            eprintln!("{:#}", error);
+        }
+    }
+
+    if let Some(path) = &args.record {
+        let Some(emulator) = emulator_device else {
+            return;
+        };
+        let mut runner = HeadlessRunner::new(emulator);
+        let result = runner.start_recording(path).and_then(|_| {
+            runner.run_frames(args.frames);
+            runner.finish_recording()
+        });
+        match result {
+            Ok(Some(path)) => log::info!(target: STATUS_TARGET, "Saved {}", path.display()),
+            Ok(None) => {}
+            Err(error) => eprintln!("{:#}", error),
+        }
+        return;
+    }
+
+    let display = DisplaySettings {
+        integer_scaling: args.integer_scale,
+        border_color: args.border_color,
+        filter: args.filter,
+        shader: args.shader,
+        frame_blend: args.frame_blend,
+        shader_dir: args.shader_dir,
+        palettes,
+        palette,
+        screenshot_dir: args.screenshot_dir,
+        recording_format: args.record_format,
+        keymap,
+    };
+
+    let _ = emulators::run(emulator_device, display);
     println!("Exiting...");
 }
+
+fn read_rom(path: &str) -> anyhow::Result<Vec<u8>> {
+    if path.is_empty() {
+        anyhow::bail!("No ROM given, pass one with --filepath <rom>");
+    }
+    std::fs::read(path).with_context(|| format!("Could not read ROM {}", path))
+}
+
+// Serves one GDB session after another until one kills the target
+fn serve_gdb(mut server: GdbServer, port: u16) -> anyhow::Result<()> {
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Could not listen on port {}", port))?;
+    loop {
+        println!("Waiting for GDB on 127.0.0.1:{}", port);
+        let (stream, peer) = listener.accept()?;
+        println!("GDB connected from {}", peer);
+        if server.serve(stream)? == Ended::Killed {
+            return Ok(());
+        }
+    }
+}
+
+fn run_command(command: &Command) -> anyhow::Result<()> {
+    match command {
+        Command::Disasm {
+            rom,
+            output,
+            dialect,
+        } => {
+            let bytes =
+                std::fs::read(rom).with_context(|| format!("Could not read {}", rom.display()))?;
+            let listing = disassemble_rom_as(&bytes, *dialect)
+                .with_context(|| format!("Could not disassemble {}", rom.display()))?
+                .to_string();
+            match output {
+                Some(path) => std::fs::write(path, listing)
+                    .with_context(|| format!("Could not write {}", path.display()))?,
+                None => print!("{}", listing),
+            }
+        }
+        Command::Asm {
+            source,
+            output,
+            dialect,
+        } => {
+            let text = std::fs::read_to_string(source)
+                .with_context(|| format!("Could not read {}", source.display()))?;
+            let rom = assemble_as(&text, *dialect)
+                .with_context(|| format!("Could not assemble {}", source.display()))?;
+            std::fs::write(output, &rom)
+                .with_context(|| format!("Could not write {}", output.display()))?;
+            println!("Wrote {} bytes to {}", rom.len(), output.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,413 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    debug::{DebugSnapshot, MEMORY_PAGE},
+    gameboy::vram::{TILE_COUNT, TILEMAP_ADDRS, TILEMAP_SIZE, VramSnapshot},
+};
+
+// Size of the debug panel in characters
+pub const PANEL_COLUMNS: usize = 32;
+pub const PANEL_ROWS: usize = 48;
+
+// Bytes per row of the hex viewer
+const MEMORY_ROW: usize = 8;
+
+// Character cells are a 5x7 glyph with a column and two rows of spacing, inside a margin
+const CELL_WIDTH: u32 = 6;
+const CELL_HEIGHT: u32 = 9;
+const MARGIN: u32 = 4;
+
+const BACKGROUND: Rgba<u8> = Rgba([20, 20, 28, 255]);
+
+// Tiles per row of the tile sheet, and how much it is scaled up
+const SHEET_COLUMNS: usize = 16;
+const SHEET_SCALE: u32 = 2;
+// Gap below each tilemap
+const MAP_SPACING: u32 = 4;
+// The outline of the screen's area on the background map
+const VIEWPORT_COLOR: Rgba<u8> = Rgba([255, 80, 80, 255]);
+
+// What the debug panel shows, cycled with F10
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverlayPage {
+    // Registers, disassembly and memory
+    Cpu,
+    Tiles,
+    Tilemaps,
+    Sprites,
+}
+
+impl OverlayPage {
+    // The page after `page`, None to hide the panel. The VRAM pages are skipped for cores
+    // without VRAM.
+    pub fn next(page: Option<Self>, has_vram: bool) -> Option<Self> {
+        match page {
+            None => Some(OverlayPage::Cpu),
+            Some(OverlayPage::Cpu) if has_vram => Some(OverlayPage::Tiles),
+            Some(OverlayPage::Tiles) => Some(OverlayPage::Tilemaps),
+            Some(OverlayPage::Tilemaps) => Some(OverlayPage::Sprites),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineStyle {
+    Heading,
+    Text,
+    // The instruction at PC
+    Current,
+}
+
+impl LineStyle {
+    fn color(self) -> Rgba<u8> {
+        match self {
+            LineStyle::Heading => Rgba([110, 170, 255, 255]),
+            LineStyle::Text => Rgba([210, 210, 210, 255]),
+            LineStyle::Current => Rgba([255, 220, 80, 255]),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanelLine {
+    pub text: String,
+    pub style: LineStyle,
+}
+
+// The panel as text: registers, the core's status lines, disassembly around PC and a page of
+// memory, wrapped or cut to `columns`
+pub fn panel_lines(snapshot: &DebugSnapshot, columns: usize) -> Vec<PanelLine> {
+    let mut lines = Vec::new();
+    let mut push = |text: String, style| lines.push(PanelLine { text, style });
+
+    push("Registers".to_string(), LineStyle::Heading);
+    let registers: Vec<String> = snapshot
+        .registers
+        .iter()
+        .map(|register| {
+            format!(
+                "{}={:0width$X}",
+                register.name.to_uppercase(),
+                register.value,
+                width = register.bits as usize / 4
+            )
+        })
+        .collect();
+    for line in wrap(&registers, columns) {
+        push(line, LineStyle::Text);
+    }
+    for status in &snapshot.status {
+        let words: Vec<String> = status.split(' ').map(str::to_string).collect();
+        for line in wrap(&words, columns) {
+            push(line, LineStyle::Text);
+        }
+    }
+
+    push(String::new(), LineStyle::Text);
+    push("Disassembly".to_string(), LineStyle::Heading);
+    for line in &snapshot.disassembly {
+        let (marker, style) = match line.current {
+            true => ('>', LineStyle::Current),
+            false => (' ', LineStyle::Text),
+        };
+        push(
+            format!("{} {:04X}: {}", marker, line.addr, line.text),
+            style,
+        );
+    }
+
+    push(String::new(), LineStyle::Text);
+    push(
+        format!("Memory {:04X} (PgUp/PgDn)", snapshot.memory_start),
+        LineStyle::Heading,
+    );
+    for (row, bytes) in snapshot.memory.chunks(MEMORY_ROW).enumerate() {
+        let mut line = format!("{:04X}:", snapshot.memory_start as usize + row * MEMORY_ROW);
+        for byte in bytes {
+            match byte {
+                Some(byte) => line.push_str(&format!(" {:02X}", byte)),
+                None => line.push_str(" --"),
+            }
+        }
+        push(line, LineStyle::Text);
+    }
+
+    for line in &mut lines {
+        if let Some((index, _)) = line.text.char_indices().nth(columns) {
+            line.text.truncate(index);
+        }
+    }
+    lines
+}
+
+// Words joined with spaces into lines of at most `columns`
+fn wrap(words: &[String], columns: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > columns {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// Where the hex viewer starts after scrolling `pages` pages from `start`
+pub fn scroll_memory(start: u16, pages: i32) -> u16 {
+    start.wrapping_add_signed((pages * MEMORY_PAGE as i32) as i16)
+}
+
+// The panel's texture for `page`, the CPU page for pages the snapshot has nothing for
+pub fn render_page(snapshot: &DebugSnapshot, page: OverlayPage) -> RgbaImage {
+    match (page, &snapshot.vram) {
+        (OverlayPage::Tiles, Some(vram)) => render_tiles(vram),
+        (OverlayPage::Tilemaps, Some(vram)) => render_tilemaps(vram),
+        (OverlayPage::Sprites, Some(vram)) => {
+            render_panel(&sprite_lines(vram), PANEL_COLUMNS, PANEL_ROWS)
+        }
+        _ => render_panel(
+            &panel_lines(snapshot, PANEL_COLUMNS),
+            PANEL_COLUMNS,
+            PANEL_ROWS,
+        ),
+    }
+}
+
+// Rasterizes `rows` by `columns` characters of `lines`, the panel's texture
+pub fn render_panel(lines: &[PanelLine], columns: usize, rows: usize) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(
+        columns as u32 * CELL_WIDTH + MARGIN * 2,
+        rows as u32 * CELL_HEIGHT + MARGIN * 2,
+        BACKGROUND,
+    );
+    for (row, line) in lines.iter().take(rows).enumerate() {
+        let text: String = line.text.chars().take(columns).collect();
+        let y = MARGIN + row as u32 * CELL_HEIGHT;
+        draw_text(&mut image, MARGIN, y, &text, line.style);
+    }
+    image
+}
+
+// Every tile in VRAM through BGP, 16 to a row in the order they are stored
+pub fn render_tiles(vram: &VramSnapshot) -> RgbaImage {
+    let rows = TILE_COUNT.div_ceil(SHEET_COLUMNS);
+    let sheet_width = (SHEET_COLUMNS * 8) as u32 * SHEET_SCALE;
+    let top = MARGIN + CELL_HEIGHT;
+    let mut image = RgbaImage::from_pixel(
+        sheet_width + MARGIN * 2,
+        top + (rows * 8) as u32 * SHEET_SCALE + MARGIN,
+        BACKGROUND,
+    );
+    draw_text(
+        &mut image,
+        MARGIN,
+        MARGIN,
+        &format!("Tiles 8000-97FF BGP={:02X}", vram.bgp),
+        LineStyle::Heading,
+    );
+    for (index, tile) in vram.tiles.iter().enumerate() {
+        let x = (index % SHEET_COLUMNS * 8) as u32;
+        let y = (index / SHEET_COLUMNS * 8) as u32;
+        for (pixel, color) in tile.iter().enumerate() {
+            let rgb = vram.color(vram.bgp, *color);
+            let px = (x + pixel as u32 % 8) * SHEET_SCALE;
+            let py = (y + pixel as u32 / 8) * SHEET_SCALE;
+            for dy in 0..SHEET_SCALE {
+                for dx in 0..SHEET_SCALE {
+                    image.put_pixel(
+                        MARGIN + px + dx,
+                        top + py + dy,
+                        Rgba([rgb[0], rgb[1], rgb[2], 255]),
+                    );
+                }
+            }
+        }
+    }
+    image
+}
+
+// Both 256x256 tilemaps through BGP, each under a heading saying what draws from it, with the
+// screen's 160x144 area outlined on the background's map where SCX and SCY put it
+pub fn render_tilemaps(vram: &VramSnapshot) -> RgbaImage {
+    let map_pixels = (TILEMAP_SIZE * 8) as u32;
+    let block = CELL_HEIGHT + map_pixels + MAP_SPACING;
+    let mut image =
+        RgbaImage::from_pixel(map_pixels + MARGIN * 2, MARGIN * 2 + block * 2, BACKGROUND);
+    for (map, tiles) in vram.tilemaps.iter().enumerate() {
+        let top = MARGIN + map as u32 * block;
+        let used_by = match (vram.background_map() == map, vram.window_map() == map) {
+            (true, true) => "BG+Window",
+            (true, false) => "BG",
+            (false, true) => "Window",
+            (false, false) => "Unused",
+        };
+        let mut heading = format!("{:04X} {}", TILEMAP_ADDRS[map], used_by);
+        if vram.background_map() == map {
+            heading.push_str(&format!(" SCX={:02X} SCY={:02X}", vram.scx, vram.scy));
+        }
+        draw_text(&mut image, MARGIN, top, &heading, LineStyle::Heading);
+
+        let top = top + CELL_HEIGHT;
+        for (position, tile) in tiles.iter().enumerate() {
+            let pixels = vram.tiles.get(*tile as usize).copied().unwrap_or([0; 64]);
+            let x = (position % TILEMAP_SIZE * 8) as u32;
+            let y = (position / TILEMAP_SIZE * 8) as u32;
+            for (pixel, color) in pixels.iter().enumerate() {
+                let rgb = vram.color(vram.bgp, *color);
+                image.put_pixel(
+                    MARGIN + x + pixel as u32 % 8,
+                    top + y + pixel as u32 / 8,
+                    Rgba([rgb[0], rgb[1], rgb[2], 255]),
+                );
+            }
+        }
+
+        if vram.background_map() == map {
+            // Wraps around the map's edges like the background does
+            let mut outline = |x: u32, y: u32| {
+                image.put_pixel(
+                    MARGIN + (vram.scx as u32 + x) % map_pixels,
+                    top + (vram.scy as u32 + y) % map_pixels,
+                    VIEWPORT_COLOR,
+                );
+            };
+            for x in 0..160 {
+                outline(x, 0);
+                outline(x, 143);
+            }
+            for y in 0..144 {
+                outline(0, y);
+                outline(159, y);
+            }
+        }
+    }
+    image
+}
+
+// One line per OAM entry: screen position, tile, palette and X flip, Y flip and behind-BG flags
+pub fn sprite_lines(vram: &VramSnapshot) -> Vec<PanelLine> {
+    let mut lines = vec![PanelLine {
+        text: format!("Sprites OBP0={:02X} OBP1={:02X}", vram.obp[0], vram.obp[1]),
+        style: LineStyle::Heading,
+    }];
+    lines.extend(vram.sprites.iter().map(|sprite| PanelLine {
+        text: sprite.to_string(),
+        style: LineStyle::Text,
+    }));
+    lines
+}
+
+fn draw_text(image: &mut RgbaImage, x: u32, y: u32, text: &str, style: LineStyle) {
+    for (column, character) in text.chars().enumerate() {
+        let x = x + column as u32 * CELL_WIDTH;
+        if x + CELL_WIDTH > image.width() {
+            break;
+        }
+        draw_glyph(image, x, y, character, style.color());
+    }
+}
+
+fn draw_glyph(image: &mut RgbaImage, x: u32, y: u32, character: char, color: Rgba<u8>) {
+    for (row, bits) in glyph(character).iter().enumerate() {
+        for column in 0..5 {
+            if bits & (0x10 >> column) != 0 {
+                image.put_pixel(x + column, y + row as u32, color);
+            }
+        }
+    }
+}
+
+pub fn has_glyph(character: char) -> bool {
+    lookup(character).is_some()
+}
+
+// Lowercase letters are drawn as capitals, except x so 0x prefixes stand out, and characters
+// without a glyph as ?
+fn glyph(character: char) -> [u8; 7] {
+    lookup(character)
+        .or_else(|| lookup('?'))
+        .unwrap_or_default()
+}
+
+fn lookup(character: char) -> Option<[u8; 7]> {
+    let character = match character {
+        'x' => 'x',
+        _ => character.to_ascii_uppercase(),
+    };
+    GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == character)
+        .map(|(_, rows)| *rows)
+}
+
+// 5x7 glyphs, one byte per row with the leftmost pixel in bit 4
+#[rustfmt::skip]
+const GLYPHS: &[(char, [u8; 7])] = &[
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    ('A', [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('x', [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11]),
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('*', [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    (';', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08]),
+    ('<', [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('>', [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('[', [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E]),
+    (']', [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    ('|', [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
@@ -0,0 +1,122 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{config, display::parse_hex_color};
+
+// Four shades in DMG order: shade 0 is the background (blank LCD), shade 3 the darkest ink.
+// CHIP-8 draws with shades 0 and 3, plus 1 and 2 for XO-CHIP's second plane.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Palette {
+    // Two colors are stretched to four by blending the middle shades between them
+    pub fn new(name: &str, colors: &[[u8; 3]]) -> Result<Self> {
+        let colors = match *colors {
+            [background, ink] => [
+                background,
+                blend(background, ink, 1, 3),
+                blend(background, ink, 2, 3),
+                ink,
+            ],
+            [a, b, c, d] => [a, b, c, d],
+            _ => bail!(
+                "Palette {} has {} colors, expected 2 or 4",
+                name,
+                colors.len()
+            ),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            colors,
+        })
+    }
+
+    // Comma separated hex colors, e.g. `#E0F8D0,#081820`
+    pub fn from_hex(name: &str, spec: &str) -> Result<Self> {
+        let colors = spec
+            .split(',')
+            .map(|color| parse_hex_color(color.trim()))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(name, &colors)
+    }
+}
+
+fn blend(from: [u8; 3], to: [u8; 3], step: u32, steps: u32) -> [u8; 3] {
+    let mut color = [0u8; 3];
+    for channel in 0..3 {
+        let (from, to) = (from[channel] as u32, to[channel] as u32);
+        color[channel] = ((from * (steps - step) + to * step) / steps) as u8;
+    }
+    color
+}
+
+pub fn builtin_palettes() -> Vec<Palette> {
+    [
+        ("dmg-green", "#E0F8D0,#88C070,#346856,#081820"),
+        ("pocket-gray", "#E3E6C9,#A8AB96,#6B6E5D,#2A2B24"),
+        ("light", "#9FF4E5,#00B9BE,#005F8C,#002B59"),
+        ("grayscale", "#FFFFFF,#AAAAAA,#555555,#000000"),
+        ("classic", "#000000,#555555,#AAAAAA,#FFFFFF"),
+    ]
+    .iter()
+    .map(|(name, spec)| Palette::from_hex(name, spec).expect("Built-in palette is invalid"))
+    .collect()
+}
+
+// Reads `[[palette]]` sections, each with a `name` and 2 or 4 hex `colors`
+pub fn load_palettes(path: &Path) -> Result<Vec<Palette>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let document =
+        config::parse(&text).with_context(|| format!("Invalid palette file {}", path.display()))?;
+
+    document
+        .sections(&["palette"])
+        .map(|section| {
+            let context = || format!("{} line {}", path.display(), section.line);
+            let name = section
+                .table
+                .str("name")
+                .with_context(context)?
+                .with_context(|| format!("{}: palette has no name", context()))?;
+            let colors = section
+                .table
+                .str_list("colors")
+                .with_context(context)?
+                .with_context(|| format!("{}: palette has no colors", context()))?;
+
+            let colors = colors
+                .iter()
+                .map(|color| parse_hex_color(color))
+                .collect::<Result<Vec<_>>>()
+                .with_context(context)?;
+            Palette::new(name, &colors).with_context(context)
+        })
+        .collect()
+}
+
+// Index of the palette called `spec`, or of a new "custom" palette if it is a list of hex colors
+pub fn select_palette(palettes: &mut Vec<Palette>, spec: &str) -> Result<usize> {
+    if let Some(index) = palettes.iter().position(|palette| palette.name == spec) {
+        return Ok(index);
+    }
+
+    let palette = Palette::from_hex("custom", spec).with_context(|| {
+        let names: Vec<_> = palettes
+            .iter()
+            .map(|palette| palette.name.as_str())
+            .collect();
+        format!(
+            "Unknown palette {}, expected one of {} or hex colors",
+            spec,
+            names.join(", ")
+        )
+    })?;
+    palettes.push(palette);
+    Ok(palettes.len() - 1)
+}
@@ -0,0 +1,563 @@
+// Chain of fragment shader passes run over the emulated screen before it is drawn.
+//
+// Each pass renders into its own texture, which becomes the next pass's input. The last
+// pass's output is what gets scaled into the window. Passes see the uniforms and bindings
+// declared in `prelude.wgsl`, including their own output from the previous frame.
+//
+// Besides the built-in presets, presets can be loaded from a directory of TOML manifests:
+//
+//     name = "soft-crt"        # optional, defaults to the file name
+//
+//     [[pass]]                 # passes run in the order they are listed
+//     shader = "blur.wgsl"     # relative to the manifest
+//     scale_type = "source"    # "source" or "viewport"
+//     scale = 2.0              # multiple of the scale_type size, 1.0 by default
+//     filter = "linear"        # how the input is sampled, "nearest" by default
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use wgpu::naga;
+
+use crate::config;
+
+const PRELUDE: &str = include_str!("postprocess/prelude.wgsl");
+
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Largest `scale` a pass may ask for
+const MAX_PASS_SCALE: f32 = 16.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PassScale {
+    // Multiple of the pass's input size
+    Source(f32),
+    // Multiple of the area the screen is drawn into
+    Viewport(f32),
+}
+
+#[derive(Clone, Debug)]
+pub struct PassDesc {
+    pub label: String,
+    // WGSL defining `fs_main`, appended to the prelude
+    pub source: String,
+    pub scale: PassScale,
+    // How the pass samples its input
+    pub filter: wgpu::FilterMode,
+}
+
+#[derive(Clone, Debug)]
+pub struct Preset {
+    pub name: String,
+    pub passes: Vec<PassDesc>,
+}
+
+pub fn builtin_presets() -> Vec<Preset> {
+    let pass = |label: &str, source: &str, scale, filter| PassDesc {
+        label: label.to_string(),
+        source: source.to_string(),
+        scale,
+        filter,
+    };
+
+    vec![
+        Preset {
+            name: "none".to_string(),
+            passes: Vec::new(),
+        },
+        Preset {
+            name: "scanlines".to_string(),
+            passes: vec![pass(
+                "scanlines",
+                include_str!("postprocess/scanlines.wgsl"),
+                PassScale::Viewport(1.0),
+                wgpu::FilterMode::Nearest,
+            )],
+        },
+        Preset {
+            name: "crt".to_string(),
+            passes: vec![pass(
+                "crt",
+                include_str!("postprocess/crt.wgsl"),
+                PassScale::Viewport(1.0),
+                wgpu::FilterMode::Linear,
+            )],
+        },
+        Preset {
+            name: "dmg-lcd".to_string(),
+            passes: vec![
+                pass(
+                    "dmg_ghosting",
+                    include_str!("postprocess/dmg_ghosting.wgsl"),
+                    PassScale::Source(1.0),
+                    wgpu::FilterMode::Nearest,
+                ),
+                pass(
+                    "dmg_grid",
+                    include_str!("postprocess/dmg_grid.wgsl"),
+                    PassScale::Viewport(1.0),
+                    wgpu::FilterMode::Nearest,
+                ),
+            ],
+        },
+    ]
+}
+
+// Compiles a pass on the CPU, so mistakes come back as readable errors instead of a wgpu panic
+pub fn validate_pass(desc: &PassDesc) -> Result<()> {
+    let source = pass_source(desc);
+
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|error| anyhow!(error.emit_to_string_with_path(&source, &desc.label)))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .map_err(|error| anyhow!(error.emit_to_string_with_path(&source, &desc.label)))?;
+
+    if !module
+        .entry_points
+        .iter()
+        .any(|entry| entry.name == "fs_main" && entry.stage == naga::ShaderStage::Fragment)
+    {
+        bail!("{} does not define a `fs_main` fragment shader", desc.label);
+    }
+
+    Ok(())
+}
+
+// The prelude goes last so line numbers in errors match the pass's own file
+fn pass_source(desc: &PassDesc) -> String {
+    format!("{}\n{}", desc.source, PRELUDE)
+}
+
+// Every `.toml` manifest directly inside `dir`, sorted by name
+pub fn find_presets(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut manifests = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            manifests.push(path);
+        }
+    }
+
+    manifests.sort();
+    Ok(manifests)
+}
+
+// Reads a manifest and the shaders it lists, checking that every pass compiles
+pub fn load_preset(manifest: &Path) -> Result<Preset> {
+    let text = fs::read_to_string(manifest)
+        .with_context(|| format!("Could not read {}", manifest.display()))?;
+    let document =
+        config::parse(&text).with_context(|| format!("Invalid manifest {}", manifest.display()))?;
+    let dir = manifest.parent().unwrap_or(Path::new("."));
+
+    let name = match document.root.str("name")? {
+        Some(name) => name.to_string(),
+        None => manifest
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+
+    let mut passes = Vec::new();
+    for section in document.sections(&["pass"]) {
+        let table = &section.table;
+        let context = || format!("{} line {}", manifest.display(), section.line);
+
+        let shader = table
+            .str("shader")
+            .with_context(context)?
+            .ok_or_else(|| anyhow!("{}: pass has no shader", context()))?;
+        let shader_path = dir.join(shader);
+        let source = fs::read_to_string(&shader_path)
+            .with_context(|| format!("Could not read {}", shader_path.display()))?;
+
+        let scale = table.f64("scale").with_context(context)?.unwrap_or(1.0) as f32;
+        if !(scale > 0.0 && scale <= MAX_PASS_SCALE) {
+            bail!(
+                "{}: scale must be above 0 and at most {}",
+                context(),
+                MAX_PASS_SCALE
+            );
+        }
+        let scale = match table.str("scale_type").with_context(context)? {
+            None | Some("source") => PassScale::Source(scale),
+            Some("viewport") => PassScale::Viewport(scale),
+            Some(other) => bail!(
+                "{}: unknown scale_type {:?}, expected source or viewport",
+                context(),
+                other
+            ),
+        };
+        let filter = match table.str("filter").with_context(context)? {
+            None | Some("nearest") => wgpu::FilterMode::Nearest,
+            Some("linear") => wgpu::FilterMode::Linear,
+            Some(other) => bail!(
+                "{}: unknown filter {:?}, expected nearest or linear",
+                context(),
+                other
+            ),
+        };
+
+        let pass = PassDesc {
+            label: shader_path.display().to_string(),
+            source,
+            scale,
+            filter,
+        };
+        validate_pass(&pass)?;
+        passes.push(pass);
+    }
+
+    if passes.is_empty() {
+        bail!("{} has no [[pass]] sections", manifest.display());
+    }
+
+    Ok(Preset { name, passes })
+}
+
+// Runs ahead of a preset's own passes when frame blending is on
+pub fn frame_blend_pass() -> PassDesc {
+    PassDesc {
+        label: "frame_blend".to_string(),
+        source: include_str!("postprocess/frame_blend.wgsl").to_string(),
+        scale: PassScale::Source(1.0),
+        filter: wgpu::FilterMode::Nearest,
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    original_size: [f32; 4],
+    source_size: [f32; 4],
+    output_size: [f32; 4],
+    frame_count: u32,
+    frame_blend: f32,
+    _padding: [u32; 2],
+}
+
+// Size and its reciprocal, the layout the shaders expect
+fn size_uniform((width, height): (u32, u32)) -> [f32; 4] {
+    let (width, height) = (width as f32, height as f32);
+    [width, height, 1.0 / width, 1.0 / height]
+}
+
+struct Target {
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    history: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    source_size: (u32, u32),
+}
+
+struct Pass {
+    label: String,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    uniforms: wgpu::Buffer,
+    scale: PassScale,
+    // Created once the input and viewport sizes are known
+    target: Option<Target>,
+}
+
+pub struct PostProcessor {
+    bind_group_layout: wgpu::BindGroupLayout,
+    passes: Vec<Pass>,
+    original_size: (u32, u32),
+    frame_count: u32,
+    frame_blend: f32,
+}
+
+impl PostProcessor {
+    // `frame_blend` is the share of the previous frame mixed into each new one, if any.
+    // Fails rather than panicking if a pass does not compile for this device.
+    pub fn new(device: &wgpu::Device, preset: &Preset, frame_blend: Option<f32>) -> Result<Self> {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("postprocess_bind_group_layout"),
+            entries: &[
+                texture_layout_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_layout_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("postprocess_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let blend_pass = frame_blend.map(|_| frame_blend_pass());
+
+        let error_scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let passes = blend_pass
+            .iter()
+            .chain(&preset.passes)
+            .map(|desc| Pass {
+                label: desc.label.clone(),
+                pipeline: create_pass_pipeline(device, &pipeline_layout, desc),
+                sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some(&desc.label),
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: desc.filter,
+                    min_filter: desc.filter,
+                    mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+                    ..Default::default()
+                }),
+                uniforms: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&desc.label),
+                    size: std::mem::size_of::<Uniforms>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                scale: desc.scale,
+                target: None,
+            })
+            .collect();
+        if let Some(error) = pollster::block_on(error_scope.pop()) {
+            bail!("Could not build shader preset {}: {}", preset.name, error);
+        }
+
+        Ok(Self {
+            bind_group_layout,
+            passes,
+            original_size: (1, 1),
+            frame_count: 0,
+            frame_blend: frame_blend.unwrap_or(0.0).clamp(0.0, 1.0),
+        })
+    }
+
+    // Recreates every pass's textures, needed whenever the screen or the viewport changes size
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        source_size: (u32, u32),
+        viewport_size: (u32, u32),
+    ) {
+        self.original_size = source_size;
+        let max_dimension = device.limits().max_texture_dimension_2d;
+
+        let mut input = source.clone();
+        let mut input_size = source_size;
+        for pass in &mut self.passes {
+            let size = pass_size(pass.scale, input_size, viewport_size, max_dimension);
+
+            let output = create_target_texture(device, &pass.label, size);
+            let history = create_target_texture(device, &pass.label, size);
+            let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+            let history_view = history.create_view(&wgpu::TextureViewDescriptor::default());
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&pass.label),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&pass.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&history_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: pass.uniforms.as_entire_binding(),
+                    },
+                ],
+            });
+
+            pass.target = Some(Target {
+                output,
+                output_view: output_view.clone(),
+                history,
+                bind_group,
+                source_size: input_size,
+            });
+            input = output_view;
+            input_size = size;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    // What to draw to the window, or None when there are no passes and the source is used as is
+    pub fn output(&self) -> Option<&wgpu::TextureView> {
+        self.passes
+            .last()
+            .and_then(|pass| pass.target.as_ref())
+            .map(|target| &target.output_view)
+    }
+
+    // Runs every pass over the current source, once per new emulated frame
+    pub fn run(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        for pass in &self.passes {
+            let Some(target) = &pass.target else {
+                continue;
+            };
+
+            let output_size = (target.output.width(), target.output.height());
+            let uniforms = Uniforms {
+                original_size: size_uniform(self.original_size),
+                source_size: size_uniform(target.source_size),
+                output_size: size_uniform(output_size),
+                frame_count: self.frame_count,
+                frame_blend: self.frame_blend,
+                _padding: [0; 2],
+            };
+            queue.write_buffer(&pass.uniforms, 0, bytemuck::bytes_of(&uniforms));
+
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(&pass.label),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target.output_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                    multiview_mask: None,
+                });
+
+                render_pass.set_pipeline(&pass.pipeline);
+                render_pass.set_bind_group(0, &target.bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+
+            // Becomes `previous` next frame
+            encoder.copy_texture_to_texture(
+                target.output.as_image_copy(),
+                target.history.as_image_copy(),
+                target.output.size(),
+            );
+        }
+
+        self.frame_count = self.frame_count.wrapping_add(1);
+    }
+}
+
+// Size of a pass's texture, clamped so that scaled passes, and passes chained off them, stay
+// within the device's texture limit
+pub fn pass_size(
+    scale: PassScale,
+    input_size: (u32, u32),
+    viewport_size: (u32, u32),
+    max_dimension: u32,
+) -> (u32, u32) {
+    let ((width, height), scale) = match scale {
+        PassScale::Source(scale) => (input_size, scale),
+        PassScale::Viewport(scale) => (viewport_size, scale),
+    };
+    let scaled = |length: u32| ((length as f32 * scale).round() as u32).clamp(1, max_dimension);
+    (scaled(width), scaled(height))
+}
+
+fn texture_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    }
+}
+
+fn create_target_texture(device: &wgpu::Device, label: &str, size: (u32, u32)) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TARGET_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn create_pass_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    desc: &PassDesc,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&desc.label),
+        source: wgpu::ShaderSource::Wgsl(pass_source(desc).into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&desc.label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: TARGET_FORMAT,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: None,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,28 @@
+// Curved glass, scanlines and an RGB aperture mask
+
+const CURVATURE: vec2<f32> = vec2<f32>(0.06, 0.08);
+
+fn curve(uv: vec2<f32>) -> vec2<f32> {
+	let centered = uv * 2.0 - 1.0;
+	let curved = centered * (1.0 + centered.yx * centered.yx * CURVATURE);
+	return curved * 0.5 + 0.5;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let uv = curve(in.tex_coords);
+	var color = textureSample(source, source_sampler, uv).rgb;
+
+	let row = fract(uv.y * uniforms.original_size.y);
+	color *= mix(0.6, 1.0, sin(row * 3.14159265));
+
+	// Every output column only lets one of red, green or blue fully through
+	let column = u32(in.clip_position.x) % 3u;
+	var mask = vec3<f32>(0.7);
+	mask[column] = 1.0;
+	color *= mask * 1.2;
+
+	// Corners bent past the edge of the screen are black
+	let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+	return vec4<f32>(select(vec3<f32>(0.0), color, inside), 1.0);
+}
@@ -0,0 +1,11 @@
+// The DMG's LCD is slow to change, so every pixel keeps part of its last value
+
+const PERSISTENCE: f32 = 0.4;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let current = textureSample(source, source_sampler, in.tex_coords).rgb;
+	let last = textureSample(previous, source_sampler, in.tex_coords).rgb;
+
+	return vec4<f32>(mix(current, last, PERSISTENCE), 1.0);
+}
@@ -0,0 +1,19 @@
+// Thin lighter gaps between the pixels of the LCD
+
+const GRID_COLOR: vec3<f32> = vec3<f32>(0.7, 0.74, 0.6);
+const GRID_STRENGTH: f32 = 0.35;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let color = textureSample(source, source_sampler, in.tex_coords).rgb;
+
+	// Output pixels from the top left corner of the emulated pixel
+	let pixel_size = uniforms.output_size.xy * uniforms.original_size.zw;
+	let offset = fract(in.tex_coords * uniforms.original_size.xy) * pixel_size;
+
+	// Only draw the grid once pixels are big enough for it to not swallow them
+	let on_grid = any(offset < vec2<f32>(1.0)) && all(pixel_size >= vec2<f32>(3.0));
+	let strength = select(0.0, GRID_STRENGTH, on_grid);
+
+	return vec4<f32>(mix(color, GRID_COLOR, strength), 1.0);
+}
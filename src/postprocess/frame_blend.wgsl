@@ -0,0 +1,9 @@
+// Mixes in what was shown last frame so flickering sprites look steady instead of blinking
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let current = textureSample(source, source_sampler, in.tex_coords).rgb;
+	let last = textureSample(previous, source_sampler, in.tex_coords).rgb;
+
+	return vec4<f32>(mix(current, last, uniforms.frame_blend), 1.0);
+}
@@ -0,0 +1,40 @@
+// Shared by every post-processing pass, which only has to provide `fs_main`
+
+struct Uniforms {
+	// Size of the emulated screen, then its reciprocal, in pixels
+	original_size: vec4<f32>,
+	// Size of this pass's input
+	source_size: vec4<f32>,
+	// Size of the texture this pass renders to
+	output_size: vec4<f32>,
+	// Number of emulated frames processed so far
+	frame_count: u32,
+	// How much of the previous frame is kept when frame blending, 0 when it is off
+	frame_blend: f32,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) tex_coords: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+// This pass's own output from the previous frame
+@group(0) @binding(2)
+var previous: texture_2d<f32>;
+@group(0) @binding(3)
+var<uniform> uniforms: Uniforms;
+
+// One triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+	let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+	var out: VertexOutput;
+	out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+	out.tex_coords = uv;
+	return out;
+}
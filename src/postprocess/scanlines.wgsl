@@ -0,0 +1,11 @@
+// Darkens the gap between emulated rows
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let color = textureSample(source, source_sampler, in.tex_coords).rgb;
+
+	let row = fract(in.tex_coords.y * uniforms.original_size.y);
+	let intensity = mix(0.55, 1.0, sin(row * 3.14159265));
+
+	return vec4<f32>(color * intensity, 1.0);
+}
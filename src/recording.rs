@@ -0,0 +1,291 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use image::{
+    Delay, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+};
+
+use crate::emulator::{AUDIO_SAMPLE_RATE, FrameView, PixelFormat};
+
+// Records every emulated frame, plus audio for formats that carry it:
+//
+// - `.gif`: animated GIF, without audio. GIF delays are in hundredths of a second and most
+//   players slow anything under two down, so frames closer together than that are dropped.
+// - `.y4m`: uncompressed 4:4:4 YUV video, with the audio next to it as 16-bit PCM in a `.wav`
+//   of the same name.
+//
+// Write errors are kept until `finish` so the emulation loop does not have to handle them.
+
+const GIF_MIN_DELAY_CENTISECONDS: u64 = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RecordingFormat {
+    #[default]
+    Gif,
+    Y4m,
+}
+
+impl RecordingFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Y4m => "y4m",
+        }
+    }
+}
+
+pub struct Recorder {
+    path: PathBuf,
+    frame_rate: f64,
+    output: Output,
+    frames: u64,
+    error: Option<anyhow::Error>,
+}
+
+enum Output {
+    Gif {
+        encoder: GifEncoder<BufWriter<File>>,
+        // Held back until the next kept frame says how long it is shown for
+        pending: Option<(RgbaImage, u64)>,
+    },
+    Y4m {
+        video: BufWriter<File>,
+        size: Option<(usize, usize)>,
+        audio: WavWriter,
+    },
+}
+
+impl Recorder {
+    // The format is picked from the extension of `path`
+    pub fn create(path: &Path, frame_rate: f64) -> Result<Self> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        let create = |path: &Path| {
+            File::create(path)
+                .map(BufWriter::new)
+                .with_context(|| format!("Could not create {}", path.display()))
+        };
+
+        let output = match extension.as_deref() {
+            Some("gif") => {
+                let mut encoder = GifEncoder::new(create(path)?);
+                encoder.set_repeat(Repeat::Infinite)?;
+                Output::Gif {
+                    encoder,
+                    pending: None,
+                }
+            }
+            Some("y4m") => Output::Y4m {
+                video: create(path)?,
+                size: None,
+                audio: WavWriter::new(create(&path.with_extension("wav"))?)?,
+            },
+            _ => bail!(
+                "Cannot record to {}, expected a .gif or .y4m file",
+                path.display()
+            ),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            frame_rate,
+            output,
+            frames: 0,
+            error: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Call once per emulated frame, whether or not the picture changed
+    pub fn push_frame(&mut self, frame: FrameView) {
+        if self.error.is_none()
+            && let Err(error) = self.write_frame(frame)
+        {
+            self.error = Some(error);
+        }
+        self.frames += 1;
+    }
+
+    pub fn push_audio(&mut self, samples: &[f32]) {
+        if let Output::Y4m { audio, .. } = &mut self.output
+            && self.error.is_none()
+            && let Err(error) = audio.write(samples)
+        {
+            self.error = Some(error);
+        }
+    }
+
+    // Flushes everything and returns the first error hit while recording
+    pub fn finish(mut self) -> Result<PathBuf> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        match self.output {
+            Output::Gif {
+                mut encoder,
+                pending,
+            } => {
+                // The last frame lasts until the end of the recording
+                if let Some((image, time)) = pending {
+                    let end = (self.frames as f64 * 100.0 / self.frame_rate).round() as u64;
+                    let delay = end.saturating_sub(time).max(GIF_MIN_DELAY_CENTISECONDS);
+                    encode_gif_frame(&mut encoder, image, delay)?;
+                }
+            }
+            Output::Y4m {
+                mut video, audio, ..
+            } => {
+                video.flush()?;
+                audio.finish()?;
+            }
+        }
+
+        Ok(self.path)
+    }
+
+    fn write_frame(&mut self, frame: FrameView) -> Result<()> {
+        let PixelFormat::Rgba8 = frame.format;
+        let frame_rate = self.frame_rate;
+        let frames = self.frames;
+
+        match &mut self.output {
+            Output::Gif { encoder, pending } => {
+                let time = (frames as f64 * 100.0 / frame_rate).round() as u64;
+                if let Some((_, pending_time)) = pending
+                    && time < *pending_time + GIF_MIN_DELAY_CENTISECONDS
+                {
+                    return Ok(());
+                }
+
+                let image =
+                    RgbaImage::from_raw(frame.width as u32, frame.height as u32, frame.data.into())
+                        .ok_or_else(|| anyhow!("Frame is smaller than its declared size"))?;
+                if let Some((previous, previous_time)) = pending.replace((image, time)) {
+                    encode_gif_frame(encoder, previous, time - previous_time)?;
+                }
+            }
+            Output::Y4m { video, size, .. } => {
+                match size {
+                    None => {
+                        write_y4m_header(video, frame.width, frame.height, frame_rate)?;
+                        *size = Some((frame.width, frame.height));
+                    }
+                    Some(size) if *size != (frame.width, frame.height) => {
+                        bail!("Y4M cannot change resolution while recording")
+                    }
+                    Some(_) => {}
+                }
+                write_y4m_frame(video, frame.data)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_gif_frame(
+    encoder: &mut GifEncoder<BufWriter<File>>,
+    image: RgbaImage,
+    centiseconds: u64,
+) -> Result<()> {
+    let delay = Delay::from_numer_denom_ms((centiseconds * 10) as u32, 1);
+    encoder.encode_frame(image::Frame::from_parts(image, 0, 0, delay))?;
+    Ok(())
+}
+
+fn write_y4m_header(
+    video: &mut impl Write,
+    width: usize,
+    height: usize,
+    frame_rate: f64,
+) -> Result<()> {
+    // Frame rate as a fraction with millihertz precision, e.g. 59.7275 Hz is 59728/1000
+    writeln!(
+        video,
+        "YUV4MPEG2 W{} H{} F{}:1000 Ip A1:1 C444",
+        width,
+        height,
+        (frame_rate * 1000.0).round() as u64
+    )?;
+    Ok(())
+}
+
+// Y, U and V planes in limited range BT.601
+fn write_y4m_frame(video: &mut impl Write, rgba: &[u8]) -> Result<()> {
+    let pixels = rgba.len() / 4;
+    let mut planes = vec![0u8; pixels * 3];
+
+    for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+        let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+        let u = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+        let v = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+
+        planes[i] = y.round() as u8;
+        planes[pixels + i] = u.round() as u8;
+        planes[pixels * 2 + i] = v.round() as u8;
+    }
+
+    video.write_all(b"FRAME\n")?;
+    video.write_all(&planes)?;
+    Ok(())
+}
+
+// Mono 16-bit PCM, with the sizes in the header filled in by `finish`
+struct WavWriter {
+    file: BufWriter<File>,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    fn new(mut file: BufWriter<File>) -> Result<Self> {
+        let byte_rate = AUDIO_SAMPLE_RATE * 2;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?; // PCM
+        file.write_all(&1u16.to_le_bytes())?; // Mono
+        file.write_all(&AUDIO_SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&byte_rate.to_le_bytes())?;
+        file.write_all(&2u16.to_le_bytes())?; // Bytes per sample
+        file.write_all(&16u16.to_le_bytes())?; // Bits per sample
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            file,
+            data_bytes: 0,
+        })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_bytes += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(36 + self.data_bytes).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_bytes.to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
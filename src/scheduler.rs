@@ -0,0 +1,50 @@
+use crate::emulator::{Emulator, MachineClock};
+
+// Drives a core from its master clock, firing timer ticks whenever the cycle count crosses a
+// timer period and stopping at frame boundaries
+pub struct Scheduler {
+    clock: MachineClock,
+    cycles: u64,
+    next_timer: u64,
+    next_frame: u64,
+}
+
+impl Scheduler {
+    pub fn new(clock: MachineClock) -> Self {
+        Self {
+            clock,
+            cycles: 0,
+            next_timer: clock.timer_period,
+            next_frame: clock.frame_period,
+        }
+    }
+
+    pub fn clock(&self) -> MachineClock {
+        self.clock
+    }
+
+    // Master clock cycles run since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn run_frame(&mut self, device: &mut dyn Emulator) {
+        while !self.step(device) {}
+    }
+
+    // Runs a single instruction, returning true if it finished a frame
+    pub fn step(&mut self, device: &mut dyn Emulator) -> bool {
+        self.cycles += device.tick() as u64;
+
+        while self.cycles >= self.next_timer {
+            device.timer_tick();
+            self.next_timer += self.clock.timer_period;
+        }
+
+        if self.cycles < self.next_frame {
+            return false;
+        }
+        self.next_frame += self.clock.frame_period;
+        true
+    }
+}
@@ -0,0 +1,166 @@
+use emulators::chip8::Chip8;
+use emulators::chip8::asm::{assemble, assemble_as};
+use emulators::chip8::debugger::Debugger;
+use emulators::chip8::disasm::{Dialect, disassemble_rom_as};
+
+// xorshift, so the ROMs are the same on every run
+fn random_rom(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn disassembly_round_trips() {
+    for dialect in [Dialect::Chip8, Dialect::SuperChip, Dialect::XoChip] {
+        for (seed, len) in [(1, 3000), (2, 1001), (3, 2)] {
+            let rom = random_rom(seed, len);
+            let listing = disassemble_rom_as(&rom, dialect).unwrap().to_string();
+            let assembled = assemble_as(&listing, dialect)
+                .unwrap_or_else(|error| panic!("{} {}: {:#}", dialect, seed, error));
+            assert_eq!(assembled, rom, "{} {}", dialect, seed);
+        }
+    }
+}
+
+#[test]
+fn extensions_round_trip_with_their_mnemonics() {
+    let source = "\
+    SCD 3
+    SCR
+    HIGH
+    LD HF, V2
+    LD R, V3
+    LD V3, R
+    SCU 1
+    SAVE V1, V4
+    LOAD V1, V4
+    PLANE 3
+    AUDIO
+    PITCH V5
+    LD I, long 0x1234
+";
+    let rom = assemble_as(source, Dialect::XoChip).unwrap();
+    assert_eq!(
+        rom,
+        [
+            0x00, 0xC3, 0x00, 0xFB, 0x00, 0xFF, 0xF2, 0x30, 0xF3, 0x75, 0xF3, 0x85, 0x00, 0xD1,
+            0x51, 0x42, 0x51, 0x43, 0xF3, 0x01, 0xF0, 0x02, 0xF5, 0x3A, 0xF0, 0x00, 0x12, 0x34
+        ]
+    );
+
+    let listing = disassemble_rom_as(&rom, Dialect::XoChip).unwrap();
+    let texts: Vec<&str> = listing
+        .lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect();
+    let expected: Vec<&str> = source.lines().map(str::trim).collect();
+    assert_eq!(texts, expected);
+}
+
+#[test]
+fn labels_constants_and_data() {
+    let rom = assemble(
+        "
+        COUNT EQU END - 1          ; constants can use labels defined later
+        start:
+            LD V0, COUNT
+            ADD V0, -1
+            LD I, sprite + 1
+            JP start
+        sprite: DB 0b1111_0000, $90, \"a;b\"
+            DW 0x1234, 7
+        END EQU 3
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        rom,
+        [
+            0x60, 0x02, 0x70, 0xFF, 0xA2, 0x09, 0x12, 0x00, 0xF0, 0x90, b'a', b';', b'b', 0x12,
+            0x34, 0x00, 0x07
+        ]
+    );
+}
+
+#[test]
+fn optional_shift_operand() {
+    assert_eq!(
+        assemble("SHR V1\nshl v2, v3").unwrap(),
+        [0x81, 0x06, 0x82, 0x3E]
+    );
+}
+
+#[test]
+fn errors_name_the_line() {
+    let error = |source: &str| format!("{:#}", assemble(source).unwrap_err());
+
+    assert_eq!(
+        error("CLS\nJP nowhere"),
+        "line 2: Unknown label or constant nowhere"
+    );
+    assert_eq!(
+        error("LD V0, 256"),
+        "line 1: Byte is 256, expected -128 to 255"
+    );
+    assert_eq!(
+        error("JP 0x1000"),
+        "line 1: Address is 4096, expected 0 to 4095"
+    );
+    assert_eq!(error("a:\na: CLS"), "line 2: a is defined twice");
+    assert_eq!(error("DT: CLS"), "line 1: DT is a reserved name");
+    assert_eq!(
+        error("MOV V0, V1"),
+        "line 1: Unknown instruction MOV V0, V1"
+    );
+    assert_eq!(error("LD I, V0"), "line 1: Unknown instruction LD I, V0");
+    assert_eq!(error("HIGH"), "line 1: HIGH needs the schip dialect");
+    assert_eq!(
+        error("PLANE 1"),
+        "line 1: PLANE 1 needs the xo-chip dialect"
+    );
+    assert_eq!(error("X EQU Y\nY EQU X\nJP X").lines().count(), 1);
+    assert!(error("X EQU Y\nY EQU X\nJP X").contains("in a loop"));
+    assert_eq!(
+        error("CLS\nDW 0x7FFFFFFFFFFFFFFF + 1"),
+        "line 2: Value out of range"
+    );
+    assert_eq!(
+        error("X EQU -0x7FFFFFFFFFFFFFFF - 0x7FFFFFFFFFFFFFFF\nJP X"),
+        "line 2: X (line 1): Value out of range"
+    );
+}
+
+#[test]
+fn roms_must_fit_in_memory() {
+    let source = "DW 0\n".repeat(1792);
+    assert!(assemble(&source).is_err());
+    assert_eq!(assemble(&"DW 0\n".repeat(1791)).unwrap().len(), 3582);
+}
+
+#[test]
+fn assembled_programs_run() {
+    let rom = assemble(
+        "
+            LD V0, 0
+        loop:
+            ADD V0, 3
+            SE V0, 9
+            JP loop
+        done: JP done
+        ",
+    )
+    .unwrap();
+
+    let mut debugger = Debugger::new(Chip8::from_rom(&rom).unwrap());
+    debugger.run(Some(0x208), 100);
+    assert_eq!(debugger.chip8().registers().v[0], 9);
+}
@@ -0,0 +1,169 @@
+use emulators::chip8::Chip8;
+use emulators::chip8::debugger::{Debugger, Fault, RamWrite, Stop, parse_hex, repl};
+use emulators::emulator::{AUDIO_SAMPLE_RATE, Emulator};
+
+// Stores the digits of 5 at 0x300 from a subroutine, then spins
+const ROM: [u16; 6] = [
+    0x6005, // 200: LD V0, 5
+    0xA300, // 202: LD I, 0x300
+    0x2208, // 204: CALL 0x208
+    0x1206, // 206: JP 0x206
+    0xF033, // 208: LD B, V0
+    0x00EE, // 20A: RET
+];
+
+fn debugger() -> Debugger {
+    let bytes: Vec<u8> = ROM.iter().flat_map(|op| op.to_be_bytes()).collect();
+    Debugger::new(Chip8::from_rom(&bytes).unwrap())
+}
+
+#[test]
+fn step_runs_one_instruction() {
+    let mut debugger = debugger();
+    assert_eq!(debugger.step(), Stop::Stepped);
+    assert_eq!(debugger.step(), Stop::Stepped);
+
+    let registers = debugger.chip8().registers();
+    assert_eq!(registers.pc, 0x204);
+    assert_eq!(registers.v[0], 5);
+    assert_eq!(registers.i, 0x300);
+    assert_eq!(debugger.instructions(), 2);
+    assert_eq!(debugger.chip8().opcode_at(0x204), 0x2208);
+}
+
+#[test]
+fn breakpoints_stop_before_the_instruction() {
+    let mut debugger = debugger();
+    debugger.add_breakpoint(0x208);
+
+    assert_eq!(debugger.run(None, 100), Stop::Breakpoint(0x208));
+    let registers = debugger.chip8().registers();
+    assert_eq!(registers.pc, 0x208);
+    assert_eq!(registers.stack, vec![0x206]);
+
+    // Resuming runs past the breakpoint it is sitting on
+    assert_eq!(debugger.run(None, 100), Stop::Limit);
+    assert!(debugger.chip8().registers().stack.is_empty());
+}
+
+#[test]
+fn watchpoints_report_ram_writes() {
+    let mut debugger = debugger();
+    debugger.add_watchpoint(0x302);
+
+    assert_eq!(
+        debugger.run(None, 100),
+        Stop::Watchpoint(RamWrite {
+            pc: 0x208,
+            addr: 0x302,
+            old: 0,
+            new: 5,
+        })
+    );
+    assert_eq!(&debugger.chip8().ram()[0x300..0x303], &[0, 0, 5]);
+
+    assert!(debugger.remove_watchpoint(0x302));
+    assert_eq!(debugger.watchpoints().count(), 0);
+}
+
+#[test]
+fn run_until_address() {
+    let mut debugger = debugger();
+    assert_eq!(debugger.run(Some(0x20A), 100), Stop::Reached(0x20A));
+    assert_eq!(debugger.instructions(), 4);
+}
+
+#[test]
+fn timers_keep_pace_with_instructions() {
+    // LD V0, 2; LD DT, V0; then spin
+    let mut chip8 = Chip8::from_rom(&[0x60, 0x02, 0xF0, 0x15, 0x12, 0x04]).unwrap();
+    chip8.set_instructions_per_frame(10);
+    let mut debugger = Debugger::new(chip8);
+
+    for _ in 0..10 {
+        debugger.step();
+    }
+    assert_eq!(debugger.chip8().registers().delay_timer, 1);
+    for _ in 0..10 {
+        debugger.step();
+    }
+    assert_eq!(debugger.chip8().registers().delay_timer, 0);
+}
+
+#[test]
+fn undrained_audio_stays_bounded() {
+    // LD V0, 0xFF; LD ST, V0; then spin, beeping for about four seconds
+    let mut debugger =
+        Debugger::new(Chip8::from_rom(&[0x60, 0xFF, 0xF0, 0x18, 0x12, 0x04]).unwrap());
+    // Ten seconds of frames, with no breakpoint to stop at
+    assert_eq!(debugger.run(None, 16 * 600), Stop::Limit);
+
+    let mut samples = Vec::new();
+    debugger.chip8_mut().drain_audio(&mut samples);
+    assert_eq!(samples.len(), AUDIO_SAMPLE_RATE as usize);
+}
+
+#[test]
+fn faults_stop_instead_of_crashing() {
+    let debugger_for = |ops: &[u16]| {
+        let bytes: Vec<u8> = ops.iter().flat_map(|op| op.to_be_bytes()).collect();
+        Debugger::new(Chip8::from_rom(&bytes).unwrap())
+    };
+
+    let mut debugger = debugger_for(&[0x1FFE]);
+    assert_eq!(
+        debugger.run(None, 100),
+        Stop::Fault(Fault::PcOutOfRange(0xFFE))
+    );
+    // The core stays put and keeps reporting it
+    assert_eq!(debugger.step(), Stop::Fault(Fault::PcOutOfRange(0xFFE)));
+
+    let mut debugger = debugger_for(&[0x00EE]);
+    assert_eq!(debugger.step(), Stop::Fault(Fault::StackUnderflow(0x200)));
+    assert_eq!(debugger.chip8().registers().pc, 0x200);
+
+    let mut debugger = debugger_for(&[0x2200]);
+    assert_eq!(
+        debugger.run(None, 100),
+        Stop::Fault(Fault::StackOverflow(0x200))
+    );
+    assert_eq!(debugger.chip8().registers().stack.len(), 15);
+
+    let mut output = Vec::new();
+    repl(&mut debugger, "continue\n".as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.contains("Fault: CALL at 0200 with a full stack"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn repl_drives_the_debugger() {
+    let mut debugger = debugger();
+    let script = "break 208\ncontinue\nregs\nmem 0x300 3\nbogus\n\nquit\nstep\n";
+    let mut output = Vec::new();
+    repl(&mut debugger, script.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(
+        output.contains("Breakpoint at 0208\n0208: F033"),
+        "{}",
+        output
+    );
+    assert!(output.contains("V0=05 V1=00"), "{}", output);
+    assert!(output.contains("Stack: 0206"), "{}", output);
+    assert!(output.contains("0300: 00 00 00\n"), "{}", output);
+    assert!(output.contains("Unknown command bogus"), "{}", output);
+    // The empty line stepped once, and nothing after quit ran
+    assert_eq!(debugger.chip8().registers().pc, 0x20A);
+}
+
+#[test]
+fn hex_arguments() {
+    assert_eq!(parse_hex("200").unwrap(), 0x200);
+    assert_eq!(parse_hex("0x2A").unwrap(), 0x2A);
+    assert_eq!(parse_hex("$ff").unwrap(), 0xFF);
+    assert!(parse_hex("zz").is_err());
+}
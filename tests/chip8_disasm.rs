@@ -0,0 +1,90 @@
+use emulators::chip8::Chip8;
+use emulators::chip8::disasm::{
+    Dialect, Line, ROM_START, TargetKind, disassemble_rom, disassemble_rom_as, target,
+};
+
+fn rom(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|op| op.to_be_bytes()).collect()
+}
+
+#[test]
+fn targets() {
+    assert_eq!(target(0x1206), Some((0x206, TargetKind::Code)));
+    assert_eq!(target(0x2208), Some((0x208, TargetKind::Subroutine)));
+    assert_eq!(target(0xA20C), Some((0x20C, TargetKind::Data)));
+    assert_eq!(target(0xB300), Some((0x300, TargetKind::Code)));
+    assert_eq!(target(0x6005), None);
+}
+
+#[test]
+fn labels_jump_call_and_data_targets() {
+    let listing = disassemble_rom(&rom(&[
+        0xA20A, // 200: LD I, sprite
+        0x2208, // 202: CALL draw
+        0x1204, // 204: JP 0x204
+        0x1300, // 206: JP outside the ROM
+        0xD015, // 208: DRW V0, V1, 5
+        0xF090, // 20A: sprite data
+    ]))
+    .unwrap();
+
+    assert_eq!(listing.lines.len(), 6);
+    assert_eq!(listing.lines[0].text, "LD I, data_20A");
+    assert_eq!(listing.lines[1].text, "CALL sub_208");
+    assert_eq!(listing.lines[2].label.as_deref(), Some("loc_204"));
+    assert_eq!(listing.lines[2].text, "JP loc_204");
+    assert_eq!(listing.lines[3].text, "JP 0x300");
+    assert_eq!(listing.lines[4].label.as_deref(), Some("sub_208"));
+    assert_eq!(listing.lines[5].text, "DW 0xF090");
+    assert_eq!(listing.lines[5].addr, ROM_START + 10);
+}
+
+#[test]
+fn calls_take_precedence_over_jumps() {
+    let listing = disassemble_rom(&rom(&[0x2204, 0x1204, 0x00EE])).unwrap();
+    assert_eq!(listing.lines[1].text, "JP sub_204");
+}
+
+#[test]
+fn odd_sized_roms_end_with_a_byte() {
+    let listing = disassemble_rom(&[0x60, 0x05, 0xAB]).unwrap();
+    assert_eq!(
+        listing.lines[1],
+        Line {
+            addr: 0x202,
+            bytes: vec![0xAB],
+            label: None,
+            text: "DB 0xAB".to_string(),
+        }
+    );
+}
+
+#[test]
+fn listing_text() {
+    let listing = disassemble_rom(&rom(&[0x6005, 0x1202])).unwrap();
+    assert_eq!(
+        listing.to_string(),
+        "    LD V0, 0x05             ; 200: 6005\n\
+         loc_202:\n\
+         \x20   JP loc_202              ; 202: 1202\n"
+    );
+}
+
+#[test]
+fn roms_past_the_end_of_memory_are_rejected() {
+    let size = Dialect::Chip8.max_rom_size();
+    let listing = disassemble_rom(&vec![0; size]).unwrap();
+    assert_eq!(listing.lines.last().unwrap().addr, 0xFFE);
+    assert!(Chip8::from_rom(&vec![0; size]).is_ok());
+
+    let error = disassemble_rom(&vec![0; size + 1]).unwrap_err();
+    assert!(error.to_string().contains("3584 bytes"), "{}", error);
+    let error = Chip8::from_rom(&vec![0; size + 1]).err().unwrap();
+    assert!(error.to_string().contains("only 3583 fit"), "{}", error);
+
+    // XO-CHIP has the whole 64K
+    let size = Dialect::XoChip.max_rom_size();
+    let listing = disassemble_rom_as(&vec![0; size], Dialect::XoChip).unwrap();
+    assert_eq!(listing.lines.last().unwrap().addr, 0xFFFE);
+    assert!(disassemble_rom_as(&vec![0; size + 1], Dialect::XoChip).is_err());
+}
@@ -0,0 +1,148 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use emulators::chip8::Chip8;
+use emulators::emulator::Emulator;
+use emulators::headless::{ScriptedInput, UPDATE_GOLDENS_ENV, fnv1a};
+use emulators::scheduler::Scheduler;
+
+// Directory holding the Timendus chip8-test-suite ROMs (the `bin` directory of the release)
+const TEST_ROMS_ENV: &str = "CHIP8_TEST_ROMS";
+
+// `<rom> <hash>` per line, the hash of a screen that was checked by eye to show only passes.
+// Screens are hashed as the core's 1-bit display rather than colors, so palettes don't matter.
+const HASHES_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/chip8_test_suite_hashes.txt"
+);
+
+struct SuiteRom {
+    name: &'static str,
+    frames: u64,
+    inputs: Vec<ScriptedInput>,
+}
+
+impl SuiteRom {
+    fn new(name: &'static str, frames: u64) -> Self {
+        Self {
+            name,
+            frames,
+            inputs: Vec::new(),
+        }
+    }
+
+    // Taps keypad `key` at `frame`, used to pick entries from the ROM menus
+    fn tap(mut self, frame: u64, key: usize) -> Self {
+        self.inputs.push(ScriptedInput::press(frame, key));
+        self.inputs.push(ScriptedInput::release(frame + 4, key));
+        self
+    }
+}
+
+fn suite() -> Vec<SuiteRom> {
+    vec![
+        SuiteRom::new("1-chip8-logo.ch8", 60),
+        SuiteRom::new("2-ibm-logo.ch8", 60),
+        SuiteRom::new("3-corax+.ch8", 120),
+        SuiteRom::new("4-flags.ch8", 120),
+        // Picks the original CHIP-8 platform from the menu
+        SuiteRom::new("5-quirks.ch8", 600).tap(30, 0x1),
+        // Picks the FX0A test, then presses key 5
+        SuiteRom::new("6-keypad.ch8", 180).tap(30, 0x3).tap(90, 0x5),
+    ]
+}
+
+// Runs `rom` frame by frame on the frontend's scheduler, applying its inputs as they come up
+fn run(rom: &SuiteRom, bytes: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::from_rom(bytes).unwrap();
+    let mut scheduler = Scheduler::new(chip8.clock());
+    for frame in 0..rom.frames {
+        for input in rom.inputs.iter().filter(|input| input.frame == frame) {
+            chip8.set_button(input.button, input.is_pressed);
+        }
+        scheduler.run_frame(&mut chip8);
+    }
+    chip8
+}
+
+// '#' for lit pixels, '.' for dark ones
+fn vbuf_ascii(vbuf: &[u8]) -> String {
+    vbuf.chunks(64)
+        .map(|row| {
+            let mut line: String = row
+                .iter()
+                .map(|&pixel| if pixel == 0 { '.' } else { '#' })
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+fn read_hashes() -> BTreeMap<String, u64> {
+    fs::read_to_string(HASHES_PATH)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (rom, hash) = line.split_once(' ')?;
+            Some((rom.to_string(), u64::from_str_radix(hash.trim(), 16).ok()?))
+        })
+        .collect()
+}
+
+fn write_hashes(hashes: &BTreeMap<String, u64>) {
+    let mut text = String::from("# 1-bit display hashes of passing Timendus test screens\n");
+    for (rom, hash) in hashes {
+        text += &format!("{} {:016x}\n", rom, hash);
+    }
+    fs::write(HASHES_PATH, text).unwrap();
+}
+
+#[test]
+fn timendus_test_suite() {
+    let Some(dir) = std::env::var_os(TEST_ROMS_ENV) else {
+        eprintln!(
+            "{} is not set, skipping the CHIP-8 test suite",
+            TEST_ROMS_ENV
+        );
+        return;
+    };
+    let dir = Path::new(&dir);
+    let update = std::env::var_os(UPDATE_GOLDENS_ENV).is_some();
+
+    let mut hashes = read_hashes();
+    let mut failures = Vec::new();
+
+    for rom in suite() {
+        let Ok(bytes) = fs::read(dir.join(rom.name)) else {
+            eprintln!("{} not found, skipping", rom.name);
+            continue;
+        };
+
+        let chip8 = run(&rom, &bytes);
+        let hash = fnv1a(chip8.vbuf());
+        let screen = vbuf_ascii(chip8.vbuf());
+
+        if update {
+            println!("{} {:016x}\n{}", rom.name, hash, screen);
+            hashes.insert(rom.name.to_string(), hash);
+            continue;
+        }
+        match hashes.get(rom.name) {
+            Some(expected) if *expected == hash => {}
+            Some(expected) => failures.push(format!(
+                "{} hashed to {:016x} instead of {:016x}:\n{}",
+                rom.name, hash, expected, screen
+            )),
+            None => failures.push(format!(
+                "{} has no hash in {}, check its screen shows only passes and run with {}=1:\n{}",
+                rom.name, HASHES_PATH, UPDATE_GOLDENS_ENV, screen
+            )),
+        }
+    }
+
+    if update {
+        write_hashes(&hashes);
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,108 @@
+use std::fs;
+
+use emulators::chip8::Chip8;
+use emulators::chip8::disasm::disassemble;
+use emulators::chip8::trace::{TraceState, trace_line};
+use emulators::emulator::EmulatorDevice;
+use emulators::headless::HeadlessRunner;
+
+#[test]
+fn disassembles_every_instruction() {
+    let cases = [
+        (0x00E0, "CLS"),
+        (0x00EE, "RET"),
+        (0x0123, "SYS 0x123"),
+        (0x1206, "JP 0x206"),
+        (0x2ABC, "CALL 0xABC"),
+        (0x3A12, "SE VA, 0x12"),
+        (0x4B34, "SNE VB, 0x34"),
+        (0x5120, "SE V1, V2"),
+        (0x6005, "LD V0, 0x05"),
+        (0x7FFF, "ADD VF, 0xFF"),
+        (0x8120, "LD V1, V2"),
+        (0x8121, "OR V1, V2"),
+        (0x8122, "AND V1, V2"),
+        (0x8123, "XOR V1, V2"),
+        (0x8124, "ADD V1, V2"),
+        (0x8125, "SUB V1, V2"),
+        (0x8126, "SHR V1, V2"),
+        (0x8127, "SUBN V1, V2"),
+        (0x812E, "SHL V1, V2"),
+        (0x9120, "SNE V1, V2"),
+        (0xA300, "LD I, 0x300"),
+        (0xB200, "JP V0, 0x200"),
+        (0xC30F, "RND V3, 0x0F"),
+        (0xD12F, "DRW V1, V2, 15"),
+        (0xE49E, "SKP V4"),
+        (0xE4A1, "SKNP V4"),
+        (0xF507, "LD V5, DT"),
+        (0xF50A, "LD V5, K"),
+        (0xF515, "LD DT, V5"),
+        (0xF518, "LD ST, V5"),
+        (0xF51E, "ADD I, V5"),
+        (0xF529, "LD F, V5"),
+        (0xF533, "LD B, V5"),
+        (0xF555, "LD [I], V5"),
+        (0xF565, "LD V5, [I]"),
+        // Not instructions
+        (0x5121, "DW 0x5121"),
+        (0x8128, "DW 0x8128"),
+        (0xE400, "DW 0xE400"),
+        (0xF5FF, "DW 0xF5FF"),
+    ];
+
+    for (opcode, text) in cases {
+        assert_eq!(disassemble(opcode), text, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn trace_lines_show_state_and_changes() {
+    let mut before = TraceState {
+        pc: 0x200,
+        v: [0; 16],
+        i: 0,
+        sp: 0,
+        delay_timer: 0,
+        sound_timer: 3,
+    };
+    before.v[0xF] = 1;
+    let mut after = TraceState {
+        pc: 0x202,
+        ..before
+    };
+    after.v[0] = 5;
+
+    assert_eq!(
+        trace_line(0x6005, &before, &after),
+        "PC:0200 OP:6005 I:0000 SP:0 DT:00 ST:03 V:00,00,00,00,00,00,00,00,00,00,00,00,00,00,00,01 \
+         ; LD V0, 0x05 ; V0=05"
+    );
+    assert!(trace_line(0x1200, &before, &before).ends_with("; JP 0x200"));
+}
+
+#[test]
+fn trace_file_gets_one_line_per_instruction() {
+    let path = std::env::temp_dir().join(format!("emulators-trace-{}.log", std::process::id()));
+    emulators::logging::init(false, Some(&path)).unwrap();
+
+    // LD V0, 5; LD I, 0x300; JP 0x204
+    let mut chip8 = Chip8::from_rom(&[0x60, 0x05, 0xA3, 0x00, 0x12, 0x04]).unwrap();
+    chip8.set_instructions_per_frame(10);
+    let mut runner = HeadlessRunner::new(EmulatorDevice::new(chip8));
+    runner.run_frames(1);
+    log::logger().flush();
+
+    let trace = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines.len(), 10);
+    assert!(lines[0].starts_with("PC:0200 OP:6005 "), "{}", lines[0]);
+    assert!(lines[1].ends_with("; LD I, 0x300 ; I=0300"), "{}", lines[1]);
+    assert!(
+        lines[9].starts_with("PC:0204 OP:1204 I:0300 "),
+        "{}",
+        lines[9]
+    );
+
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,73 @@
+use emulators::config::{Value, parse};
+
+#[test]
+fn parses_tables_and_values() {
+    let document = parse(
+        r##"
+name = "test" # trailing comment
+speed = 1.5
+enabled = true
+
+[chip8]
+colors = [
+    "#88C070", # multi-line arrays may have comments
+    '#081820',
+]
+
+[chip8.rom."PONG.ch8"]
+ipf = 0x10
+
+[[pass]]
+shader = "a.wgsl"
+
+[[pass]]
+shader = "b.wgsl"
+"##,
+    )
+    .unwrap();
+
+    assert_eq!(document.root.str("name").unwrap(), Some("test"));
+    assert_eq!(document.root.f64("speed").unwrap(), Some(1.5));
+    assert_eq!(document.root.bool("enabled").unwrap(), Some(true));
+
+    let chip8 = &document.section(&["chip8"]).unwrap().table;
+    assert_eq!(
+        chip8.str_list("colors").unwrap(),
+        Some(vec!["#88C070", "#081820"])
+    );
+
+    let rom = &document
+        .section(&["chip8", "rom", "PONG.ch8"])
+        .unwrap()
+        .table;
+    assert_eq!(rom.get("ipf").unwrap().value, Value::Number(16.0));
+
+    let shaders: Vec<_> = document
+        .sections(&["pass"])
+        .map(|section| section.table.str("shader").unwrap().unwrap())
+        .collect();
+    assert_eq!(shaders, ["a.wgsl", "b.wgsl"]);
+}
+
+#[test]
+fn reports_line_numbers() {
+    let error = parse("a = 1\nb = \"unterminated\n").unwrap_err();
+    assert!(error.to_string().starts_with("line 2:"), "{}", error);
+
+    let error = parse("a = 1\n\n[x]\n[x]\n").unwrap_err();
+    assert!(error.to_string().contains("line 4"), "{}", error);
+
+    let document = parse("a = 1\nb = true\n").unwrap();
+    let error = document.root.str("b").unwrap_err();
+    assert!(error.to_string().contains("line 2"), "{}", error);
+}
+
+#[test]
+fn rejects_numbers_that_are_not_finite() {
+    for word in ["nan", "inf", "-inf", "1e999"] {
+        let error = parse(&format!("scale = {}\n", word)).unwrap_err();
+        assert!(error.to_string().contains("invalid value"), "{}", error);
+    }
+    let document = parse("scale = 1e300\n").unwrap();
+    assert_eq!(document.root.f64("scale").unwrap(), Some(1e300));
+}
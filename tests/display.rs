@@ -0,0 +1,43 @@
+use emulators::display::{Viewport, fit_viewport, parse_hex_color};
+
+#[test]
+fn fractional_scaling_fills_one_axis() {
+    // 64x32 in a 640x480 window is limited by the width
+    assert_eq!(
+        fit_viewport((64, 32), (640, 480), false),
+        Viewport {
+            x: 0.0,
+            y: 80.0,
+            width: 640.0,
+            height: 320.0,
+        }
+    );
+}
+
+#[test]
+fn integer_scaling_rounds_down_and_centres() {
+    // 160x144 fits 3.33x by height, so 3x with bars on every side
+    assert_eq!(
+        fit_viewport((160, 144), (800, 480), true),
+        Viewport {
+            x: 160.0,
+            y: 24.0,
+            width: 480.0,
+            height: 432.0,
+        }
+    );
+}
+
+#[test]
+fn integer_scaling_shrinks_below_one() {
+    let viewport = fit_viewport((160, 144), (80, 72), true);
+    assert_eq!((viewport.width, viewport.height), (80.0, 72.0));
+}
+
+#[test]
+fn parses_hex_colors() {
+    assert_eq!(parse_hex_color("#88C070").unwrap(), [0x88, 0xC0, 0x70]);
+    assert_eq!(parse_hex_color("081820").unwrap(), [0x08, 0x18, 0x20]);
+    assert!(parse_hex_color("#12345").is_err());
+    assert!(parse_hex_color("#GG0000").is_err());
+}
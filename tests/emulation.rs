@@ -0,0 +1,186 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use emulators::emulation::{EmulationThread, EmulatorCommand, Speed};
+use emulators::emulator::{Emulator, EmulatorDevice, FrameView, MachineClock, PixelFormat};
+
+// Reports how many frames it has run as its single pixel
+#[derive(Default)]
+struct FrameCounter {
+    pixel: [u8; 4],
+}
+
+impl Emulator for FrameCounter {
+    fn tick(&mut self) -> u32 {
+        1
+    }
+
+    fn clock(&self) -> MachineClock {
+        MachineClock {
+            hz: 60,
+            timer_period: 1,
+            frame_period: 1,
+        }
+    }
+
+    fn framebuffer(&self) -> FrameView<'_> {
+        FrameView {
+            data: &self.pixel,
+            width: 1,
+            height: 1,
+            format: PixelFormat::Rgba8,
+        }
+    }
+
+    fn take_frame_ready(&mut self) -> bool {
+        true
+    }
+
+    fn timer_tick(&mut self) {
+        self.pixel[0] = self.pixel[0].wrapping_add(1);
+    }
+}
+
+fn wait_for_frame(emulation: &mut EmulationThread) -> Option<u8> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+        if let Some(frame) = emulation.frames().read() {
+            return Some(frame.data[0]);
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    None
+}
+
+#[test]
+fn pause_and_advance_frame() {
+    let mut emulation = EmulationThread::spawn(EmulatorDevice::new(FrameCounter::default()));
+    let mut paused_at = wait_for_frame(&mut emulation).expect("No frame before pausing");
+    emulation.send(EmulatorCommand::SetPaused(true));
+
+    // Whatever ran before the pause landed has been published once this settles
+    thread::sleep(Duration::from_millis(100));
+    if let Some(frame) = emulation.frames().read() {
+        paused_at = frame.data[0];
+    }
+    thread::sleep(Duration::from_millis(100));
+    assert!(emulation.frames().read().is_none());
+
+    emulation.send(EmulatorCommand::AdvanceFrame);
+    let advanced = wait_for_frame(&mut emulation).unwrap();
+    assert_eq!(advanced, paused_at.wrapping_add(1));
+
+    thread::sleep(Duration::from_millis(100));
+    assert!(emulation.frames().read().is_none());
+}
+
+#[test]
+fn uncapped_runs_faster_than_real_time() {
+    let emulation = EmulationThread::spawn(EmulatorDevice::new(FrameCounter::default()));
+    emulation.send(EmulatorCommand::SetSpeed(Speed::Uncapped));
+
+    thread::sleep(Duration::from_millis(1200));
+    assert!(emulation.measured_speed() > 2.0);
+}
+
+#[test]
+fn debug_view_publishes_snapshots_while_paused() {
+    let chip8 = emulators::chip8::Chip8::from_rom(&[0x60, 0x05]).unwrap();
+    let emulation = EmulationThread::spawn(EmulatorDevice::new(chip8));
+    emulation.send(EmulatorCommand::SetPaused(true));
+    emulation.send(EmulatorCommand::SetDebugView(Some(0x200)));
+
+    let start = Instant::now();
+    let snapshot = loop {
+        if let Some(snapshot) = emulation.take_debug_snapshot() {
+            break snapshot;
+        }
+        assert!(start.elapsed() < Duration::from_secs(2), "No snapshot");
+        thread::sleep(Duration::from_millis(1));
+    };
+    assert_eq!(snapshot.memory_start, 0x200);
+    assert_eq!(snapshot.memory[0], Some(0x60));
+
+    // Scrolling publishes again even though no frame runs
+    emulation.send(EmulatorCommand::SetDebugView(Some(0x280)));
+    let start = Instant::now();
+    while emulation
+        .take_debug_snapshot()
+        .is_none_or(|snapshot| snapshot.memory_start != 0x280)
+    {
+        assert!(start.elapsed() < Duration::from_secs(2), "No snapshot");
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    emulation.send(EmulatorCommand::SetDebugView(None));
+    thread::sleep(Duration::from_millis(20));
+    emulation.take_debug_snapshot();
+    emulation.send(EmulatorCommand::AdvanceFrame);
+    thread::sleep(Duration::from_millis(50));
+    assert!(emulation.take_debug_snapshot().is_none());
+}
+
+#[test]
+fn emulated_frames_count_unchanged_pictures_until_paused() {
+    // JP 0x200 never draws
+    let chip8 = emulators::chip8::Chip8::from_rom(&[0x12, 0x00]).unwrap();
+    let mut emulation = EmulationThread::spawn(EmulatorDevice::new(chip8));
+
+    let start = Instant::now();
+    while emulation.emulated_frames() < 5 {
+        assert!(start.elapsed() < Duration::from_secs(2), "No frames ran");
+        thread::sleep(Duration::from_millis(1));
+    }
+    // At most the initial blank screen was published
+    emulation.frames().read();
+    assert!(emulation.frames().read().is_none());
+
+    emulation.send(EmulatorCommand::SetPaused(true));
+    thread::sleep(Duration::from_millis(50));
+    let paused_at = emulation.emulated_frames();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(emulation.emulated_frames(), paused_at);
+
+    emulation.send(EmulatorCommand::AdvanceFrame);
+    let start = Instant::now();
+    while emulation.emulated_frames() == paused_at {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "Frame did not advance"
+        );
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(emulation.emulated_frames(), paused_at + 1);
+}
+
+#[test]
+fn recording_state_follows_the_recorder() {
+    let emulation = EmulationThread::spawn(EmulatorDevice::new(FrameCounter::default()));
+    let settles_on = |recording: bool| {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(2) {
+            if emulation.is_recording() == recording {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        false
+    };
+
+    let dir = std::env::temp_dir().join(format!("emulators-rec-{}", std::process::id()));
+    emulation.send(EmulatorCommand::StartRecording(
+        dir.join("missing/frames.y4m"),
+    ));
+    // Given time to try, the failed start leaves it off
+    thread::sleep(Duration::from_millis(50));
+    assert!(!emulation.is_recording());
+
+    std::fs::create_dir_all(&dir).unwrap();
+    emulation.send(EmulatorCommand::StartRecording(dir.join("frames.y4m")));
+    assert!(settles_on(true));
+    emulation.send(EmulatorCommand::StopRecording);
+    assert!(settles_on(false));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
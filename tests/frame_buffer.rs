@@ -0,0 +1,52 @@
+use std::thread;
+
+use emulators::frame_buffer::frame_buffer;
+
+fn publish(value: u8) -> impl FnOnce(&mut emulators::frame_buffer::Frame) {
+    move |frame| {
+        frame.data = vec![value; 4];
+        frame.width = 2;
+        frame.height = 2;
+    }
+}
+
+#[test]
+fn reader_sees_newest_frame_once() {
+    let (mut writer, mut reader) = frame_buffer();
+    assert!(reader.read().is_none());
+
+    writer.write(publish(1));
+    writer.write(publish(2));
+    writer.write(publish(3));
+
+    assert_eq!(reader.read().unwrap().data, vec![3; 4]);
+    assert!(reader.read().is_none());
+    assert_eq!(reader.latest().data, vec![3; 4]);
+
+    writer.write(publish(4));
+    assert_eq!(reader.read().unwrap().data, vec![4; 4]);
+}
+
+#[test]
+fn frames_are_never_torn() {
+    let (mut writer, mut reader) = frame_buffer();
+
+    let producer = thread::spawn(move || {
+        for value in 0..=255u8 {
+            for _ in 0..100 {
+                writer.write(|frame| frame.data = vec![value; 1024]);
+            }
+        }
+    });
+
+    let mut last = 0;
+    while !producer.is_finished() {
+        if let Some(frame) = reader.read() {
+            let value = frame.data[0];
+            assert!(frame.data.iter().all(|&byte| byte == value));
+            assert!(value >= last);
+            last = value;
+        }
+    }
+    producer.join().unwrap();
+}
@@ -0,0 +1,130 @@
+use std::path::Path;
+
+use emulators::gameboy::test_rom::{
+    DEFAULT_MAX_TICKS, TestOutcome, TestRomKind, find_test_roms, format_table, run_test_rom,
+    run_test_rom_file,
+};
+
+// Directory holding the Blargg and Mooneye ROMs, e.g. a checkout of c-sp/gameboy-test-roms
+const TEST_ROMS_ENV: &str = "GB_TEST_ROMS";
+
+// ROMs (relative to GB_TEST_ROMS) that are known to pass and must keep passing
+const EXPECTED_PASSES: &str = include_str!("gameboy_expected_passes.txt");
+
+fn rom_with_code(code: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(code);
+    rom
+}
+
+// LD H, hi; LD L, lo; LD (HL), val
+fn store(addr: u16, val: u8) -> [u8; 6] {
+    [0x26, (addr >> 8) as u8, 0x2E, addr as u8, 0x36, val]
+}
+
+#[test]
+fn mooneye_pass_pattern() {
+    let rom = rom_with_code(&[
+        0x06, 3, // LD B, 3
+        0x0E, 5, // LD C, 5
+        0x16, 8, // LD D, 8
+        0x1E, 13, // LD E, 13
+        0x26, 21, // LD H, 21
+        0x2E, 34, // LD L, 34
+        0x40, 0x00, // LD B, B; NOP
+    ]);
+
+    assert_eq!(
+        run_test_rom(&rom, TestRomKind::Mooneye, 100),
+        TestOutcome::Passed
+    );
+}
+
+#[test]
+fn mooneye_fail_pattern() {
+    let rom = rom_with_code(&[
+        0x06, 0x42, 0x0E, 0x42, 0x16, 0x42, 0x1E, 0x42, 0x26, 0x42, 0x2E, 0x42, 0x40,
+    ]);
+
+    assert_eq!(
+        run_test_rom(&rom, TestRomKind::Mooneye, 100),
+        TestOutcome::Failed(String::new())
+    );
+}
+
+#[test]
+fn mooneye_timeout_without_breakpoint() {
+    let rom = rom_with_code(&[]);
+
+    assert_eq!(
+        run_test_rom(&rom, TestRomKind::Mooneye, 100),
+        TestOutcome::TimedOut
+    );
+}
+
+#[test]
+fn blargg_signature() {
+    let mut code = Vec::new();
+    code.extend(store(0xA000, 0x80));
+    code.extend(store(0xA001, 0xDE));
+    code.extend(store(0xA002, 0xB0));
+    code.extend(store(0xA003, 0x61));
+    code.extend(store(0xA004, b'X'));
+    code.extend(store(0xA000, 0x01));
+
+    assert_eq!(
+        run_test_rom(&rom_with_code(&code), TestRomKind::Blargg, 100),
+        TestOutcome::Failed("code 1: X".to_string())
+    );
+}
+
+#[test]
+fn blargg_serial() {
+    let mut code = Vec::new();
+    for byte in b"cpu_instrs\n\nPassed\n" {
+        code.extend(store(0xFF01, *byte));
+        code.extend(store(0xFF02, 0x81));
+    }
+
+    assert_eq!(
+        run_test_rom(&rom_with_code(&code), TestRomKind::Blargg, 1000),
+        TestOutcome::Passed
+    );
+}
+
+// Runs every ROM under GB_TEST_ROMS and prints a pass/fail table (use --nocapture)
+#[test]
+fn test_rom_suite() {
+    let Some(dir) = std::env::var_os(TEST_ROMS_ENV) else {
+        eprintln!(
+            "{} is not set, skipping the GameBoy test ROM suite",
+            TEST_ROMS_ENV
+        );
+        return;
+    };
+    let dir = Path::new(&dir);
+
+    let results: Vec<_> = find_test_roms(dir)
+        .unwrap()
+        .iter()
+        .map(|rom| run_test_rom_file(rom, DEFAULT_MAX_TICKS).unwrap())
+        .collect();
+    println!("{}", format_table(&results));
+
+    let regressions: Vec<_> = EXPECTED_PASSES
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter(|rom| {
+            let name = dir.join(rom).display().to_string();
+            !results
+                .iter()
+                .any(|result| result.name == name && result.outcome == TestOutcome::Passed)
+        })
+        .collect();
+    assert!(
+        regressions.is_empty(),
+        "No longer passing: {:?}",
+        regressions
+    );
+}
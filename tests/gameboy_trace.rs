@@ -0,0 +1,129 @@
+use std::fs;
+
+use emulators::emulator::Emulator;
+use emulators::gameboy::GameBoy;
+use emulators::gameboy::disasm::{disassemble, disassemble_cb};
+use emulators::gameboy::trace::doctor_line;
+
+fn disassemble_bytes(addr: u16, bytes: &[u8]) -> (String, u16) {
+    disassemble(addr, |at| bytes[(at - addr) as usize])
+}
+
+// A 32 KiB ROM with `code` at the entry point
+fn rom_with(code: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(code);
+    rom
+}
+
+#[test]
+fn disassembles_base_instructions() {
+    let cases: [(&[u8], &str); 30] = [
+        (&[0x00], "NOP"),
+        (&[0x01, 0x34, 0x12], "LD BC, 0x1234"),
+        (&[0x02], "LD (BC), A"),
+        (&[0x08, 0x00, 0xC0], "LD (0xC000), SP"),
+        (&[0x10, 0x00], "STOP"),
+        (&[0x18, 0xFE], "JR 0x0100"),
+        (&[0x20, 0x05], "JR NZ, 0x0107"),
+        (&[0x22], "LD (HL+), A"),
+        (&[0x2A], "LD A, (HL+)"),
+        (&[0x32], "LD (HL-), A"),
+        (&[0x39], "ADD HL, SP"),
+        (&[0x3C], "INC A"),
+        (&[0x35], "DEC (HL)"),
+        (&[0x36, 0x7F], "LD (HL), 0x7F"),
+        (&[0x27], "DAA"),
+        (&[0x41], "LD B, C"),
+        (&[0x76], "HALT"),
+        (&[0x86], "ADD A, (HL)"),
+        (&[0x9F], "SBC A, A"),
+        (&[0xAF], "XOR A"),
+        (&[0xC0], "RET NZ"),
+        (&[0xC3, 0x50, 0x01], "JP 0x0150"),
+        (&[0xC5], "PUSH BC"),
+        (&[0xCD, 0x00, 0x20], "CALL 0x2000"),
+        (&[0xD9], "RETI"),
+        (&[0xE0, 0x44], "LDH (0xFF44), A"),
+        (&[0xE8, 0xFE], "ADD SP, -2"),
+        (&[0xF1], "POP AF"),
+        (&[0xF8, 0x02], "LD HL, SP+2"),
+        (&[0xFF], "RST 0x38"),
+    ];
+    for (bytes, text) in cases {
+        assert_eq!(
+            disassemble_bytes(0x100, bytes),
+            (text.to_string(), bytes.len() as u16),
+            "{:02X?}",
+            bytes
+        );
+    }
+}
+
+#[test]
+fn disassembles_cb_instructions() {
+    assert_eq!(disassemble_cb(0x00), "RLC B");
+    assert_eq!(disassemble_cb(0x37), "SWAP A");
+    assert_eq!(disassemble_cb(0x3E), "SRL (HL)");
+    assert_eq!(disassemble_cb(0x7C), "BIT 7, H");
+    assert_eq!(disassemble_cb(0x87), "RES 0, A");
+    assert_eq!(disassemble_cb(0xFE), "SET 7, (HL)");
+    assert_eq!(
+        disassemble_bytes(0x100, &[0xCB, 0x11]),
+        ("RL C".to_string(), 2)
+    );
+}
+
+#[test]
+fn unused_opcodes_are_data() {
+    let unused = [
+        0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+    ];
+    for opcode in 0..=0xFFu8 {
+        let (text, len) = disassemble_bytes(0, &[opcode, 0, 0]);
+        assert_eq!(
+            text.starts_with("DB "),
+            unused.contains(&opcode),
+            "{:02X}: {}",
+            opcode,
+            text
+        );
+        assert!((1..=3).contains(&len), "{:02X}: {}", opcode, len);
+    }
+}
+
+#[test]
+fn doctor_line_matches_the_boot_state() {
+    let gameboy = GameBoy::from_rom(&rom_with(&[0x00, 0xC3, 0x50, 0x01]));
+    assert_eq!(
+        doctor_line(&gameboy),
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01"
+    );
+}
+
+#[test]
+fn trace_file_gets_one_doctor_line_per_instruction() {
+    let path = std::env::temp_dir().join(format!(
+        "emulators-gameboy-trace-{}.log",
+        std::process::id()
+    ));
+    emulators::logging::init(false, Some(&path)).unwrap();
+
+    // LD B, 0x42; NOP
+    let mut gameboy = GameBoy::from_rom(&rom_with(&[0x06, 0x42, 0x00]));
+    gameboy.tick();
+    gameboy.tick();
+    log::logger().flush();
+
+    let trace = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:06,42,00,00",
+            "A:01 F:B0 B:42 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0102 PCMEM:00,00,00,00",
+        ]
+    );
+
+    fs::remove_file(path).unwrap();
+}
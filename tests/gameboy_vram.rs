@@ -0,0 +1,146 @@
+use emulators::chip8::Chip8;
+use emulators::debug::DebugSnapshot;
+use emulators::gameboy::GameBoy;
+use emulators::gameboy::vram::{SPRITE_COUNT, TILE_COUNT, TILEMAP_SIZE};
+use emulators::overlay::{OverlayPage, render_tilemaps, render_tiles, sprite_lines};
+
+fn gameboy() -> GameBoy {
+    let mut gameboy = GameBoy::from_rom(&vec![0; 0x8000]);
+    // Identity BGP: color n is shade n
+    gameboy.write(0xFF47, 0b11_10_01_00);
+    gameboy
+}
+
+// Writes the two bitplanes of each row of tile `index`
+fn write_tile(gameboy: &mut GameBoy, index: u16, rows: [(u8, u8); 8]) {
+    for (row, (low, high)) in rows.iter().enumerate() {
+        let addr = 0x8000 + index * 16 + row as u16 * 2;
+        gameboy.write(addr, *low);
+        gameboy.write(addr + 1, *high);
+    }
+}
+
+#[test]
+fn tiles_are_decoded_from_two_bitplanes() {
+    let mut gameboy = gameboy();
+    // Colors 0, 1, 2, 3 from the left in the first row
+    write_tile(&mut gameboy, 1, [(0b0101_0000, 0b0011_0000); 8]);
+    let tile = gameboy.tile(1);
+    assert_eq!(tile[..8], [0, 1, 2, 3, 0, 0, 0, 0]);
+    assert_eq!(tile[8..12], [0, 1, 2, 3]);
+    assert_eq!(gameboy.tile(0), [0; 64]);
+
+    let vram = gameboy.vram_snapshot();
+    assert_eq!(vram.tiles.len(), TILE_COUNT);
+    assert_eq!(vram.tiles[1], tile);
+}
+
+#[test]
+fn tilemaps_follow_the_lcdc_addressing_mode() {
+    let mut gameboy = gameboy();
+    gameboy.write(0x9800, 0x05);
+    gameboy.write(0x9801, 0x80);
+    gameboy.write(0x9C00, 0xFF);
+
+    // LCDC bit 4 clear: signed from 0x9000
+    let vram = gameboy.vram_snapshot();
+    assert_eq!(vram.tilemaps[0].len(), TILEMAP_SIZE * TILEMAP_SIZE);
+    assert_eq!(vram.tilemaps[0][..2], [261, 128]);
+    assert_eq!(vram.tilemaps[1][0], 255);
+    assert_eq!(vram.background_map(), 0);
+
+    // Bit 4 set: unsigned from 0x8000, bit 3 moves the background to 0x9C00
+    gameboy.write(0xFF40, 0x98);
+    let vram = gameboy.vram_snapshot();
+    assert_eq!(vram.tilemaps[0][..2], [5, 128]);
+    assert_eq!(vram.background_map(), 1);
+    assert_eq!(vram.window_map(), 0);
+}
+
+#[test]
+fn sprites_come_from_oam() {
+    let mut gameboy = gameboy();
+    for (offset, value) in [0x10u8, 0x08, 0x2A, 0b1011_0000].iter().enumerate() {
+        gameboy.write(0xFE04 + offset as u16, *value);
+    }
+    let sprites = gameboy.sprites();
+    assert_eq!(sprites.len(), SPRITE_COUNT);
+    let sprite = sprites[1];
+    assert_eq!((sprite.screen_x(), sprite.screen_y()), (0, 0));
+    assert_eq!(sprite.tile, 0x2A);
+    assert!(sprite.behind_background() && sprite.flip_x() && !sprite.flip_y());
+    assert_eq!(sprite.palette(), 1);
+    assert_eq!(sprite.to_string(), "01 X=+000 Y=+000 T=2A OBP1 X-B");
+    assert_eq!(sprites[0].to_string(), "00 X=-008 Y=-016 T=00 OBP0 ---");
+
+    let lines = sprite_lines(&gameboy.vram_snapshot());
+    assert_eq!(lines.len(), SPRITE_COUNT + 1);
+    assert_eq!(lines[2].text, sprite.to_string());
+}
+
+#[test]
+fn tile_sheet_uses_bgp() {
+    let mut gameboy = gameboy();
+    write_tile(&mut gameboy, 0, [(0xFF, 0xFF); 8]);
+    let image = render_tiles(&gameboy.vram_snapshot());
+    // 16 tiles of 8 pixels at 2x, 24 rows, under a heading
+    assert_eq!(image.dimensions(), (256 + 8, 4 + 9 + 384 + 4));
+    assert_eq!(image.get_pixel(4, 13).0, [0, 0, 0, 255]);
+    assert_eq!(image.get_pixel(4 + 16, 13).0, [255, 255, 255, 255]);
+
+    // Inverted BGP
+    gameboy.write(0xFF47, 0b00_01_10_11);
+    let image = render_tiles(&gameboy.vram_snapshot());
+    assert_eq!(image.get_pixel(4, 13).0, [255, 255, 255, 255]);
+    assert_eq!(image.get_pixel(4 + 16, 13).0, [0, 0, 0, 255]);
+}
+
+#[test]
+fn tilemaps_outline_the_screen_at_the_scroll_position() {
+    let mut gameboy = gameboy();
+    gameboy.write(0xFF42, 0x10);
+    gameboy.write(0xFF43, 200);
+    let image = render_tilemaps(&gameboy.vram_snapshot());
+    assert_eq!(image.dimensions(), (256 + 8, 8 + (9 + 256 + 4) * 2));
+
+    let red = [255, 80, 80, 255];
+    let map_top = 4 + 9;
+    // Top left corner, and the right edge wrapped round to x = (200 + 159) % 256
+    assert_eq!(image.get_pixel(4 + 200, map_top + 0x10).0, red);
+    assert_eq!(image.get_pixel(4 + 103, map_top + 0x20).0, red);
+    assert_eq!(
+        image.get_pixel(4 + 100, map_top + 0x20).0,
+        [255, 255, 255, 255]
+    );
+    // Not on the window's map
+    let second_top = map_top + 256 + 4 + 9;
+    assert!((0..256).all(|x| image.get_pixel(4 + x, second_top + 0x10).0 != red));
+}
+
+#[test]
+fn vram_pages_are_only_offered_with_vram() {
+    assert!(
+        DebugSnapshot::capture(&Chip8::from_rom(&[]).unwrap(), 0)
+            .vram
+            .is_none()
+    );
+    assert!(DebugSnapshot::capture(&gameboy(), 0).vram.is_some());
+
+    assert_eq!(OverlayPage::next(None, false), Some(OverlayPage::Cpu));
+    assert_eq!(OverlayPage::next(Some(OverlayPage::Cpu), false), None);
+    let mut page = Some(OverlayPage::Cpu);
+    let mut pages = Vec::new();
+    while let Some(current) = page {
+        pages.push(current);
+        page = OverlayPage::next(page, true);
+    }
+    assert_eq!(
+        pages,
+        [
+            OverlayPage::Cpu,
+            OverlayPage::Tiles,
+            OverlayPage::Tilemaps,
+            OverlayPage::Sprites
+        ]
+    );
+}
@@ -0,0 +1,189 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+
+use emulators::chip8::Chip8;
+use emulators::debug::DebugTarget;
+use emulators::gameboy::GameBoy;
+use emulators::gdb::{Ended, GdbServer, target_xml};
+
+// Serves one session on a free port, returning how it ended
+fn start(target: impl DebugTarget + 'static) -> (Client, JoinHandle<Ended>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut server = GdbServer::new(target);
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        server.serve(stream).unwrap()
+    });
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let client = Client { stream };
+    (client, handle)
+}
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn send_raw(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).unwrap();
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        self.send_raw(format!("${}#{:02x}", data, checksum).as_bytes());
+    }
+
+    fn command(&mut self, data: &str) -> String {
+        self.send(data);
+        self.reply()
+    }
+
+    // Skips acks and returns the next packet's data, checking its checksum
+    fn reply(&mut self) -> String {
+        let mut byte = [0];
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+        let mut data = Vec::new();
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        let expected = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        assert_eq!(
+            u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+            expected
+        );
+        self.send_raw(b"+");
+        String::from_utf8(data).unwrap()
+    }
+}
+
+#[test]
+fn chip8_registers_memory_and_stepping() {
+    // LD V0, 5; LD I, 0x300; JP 0x204
+    let (mut gdb, server) = start(Chip8::from_rom(&[0x60, 0x05, 0xA3, 0x00, 0x12, 0x04]).unwrap());
+
+    assert!(gdb.command("qSupported:swbreak+").contains("PacketSize="));
+    assert_eq!(gdb.command("?"), "S05");
+    // V0-VF, I, PC (big-endian), SP, DT and ST
+    assert_eq!(
+        gdb.command("g"),
+        format!("{}00000200000000", "00".repeat(16))
+    );
+    assert_eq!(gdb.command("m200,4"), "6005a300");
+
+    assert_eq!(gdb.command("s"), "S05");
+    assert_eq!(gdb.command("p0"), "05");
+    assert_eq!(gdb.command("p11"), "0202");
+    assert_eq!(gdb.command("s"), "S05");
+    assert_eq!(gdb.command("p10"), "0300");
+
+    assert_eq!(gdb.command("P1=7f"), "OK");
+    assert_eq!(gdb.command("p1"), "7f");
+    assert_eq!(gdb.command("p99"), "E0e");
+
+    assert_eq!(gdb.command("M300,2:abcd"), "OK");
+    assert_eq!(gdb.command("m300,2"), "abcd");
+    // RAM ends at 0xFFE, reads stop there
+    assert_eq!(gdb.command("mffd,4"), "0000");
+    assert_eq!(gdb.command("m1000,1"), "E0e");
+    assert_eq!(gdb.command("M1000,1:00"), "E0e");
+    // Addresses past the 16-bit space are bad rather than wrapping around
+    assert_eq!(gdb.command("mffffffffffffffff,10"), "E0e");
+    assert_eq!(gdb.command("Mffffffffffffffff,2:0000"), "E0e");
+    assert_eq!(gdb.command("m300,1"), "ab");
+
+    // PC values that can't be fetched from are refused, and stepping carries on from 0x204
+    assert_eq!(gdb.command("P11=0ffe"), "E0e");
+    assert_eq!(gdb.command("s0fff"), "E0e");
+    assert_eq!(gdb.command("s"), "S05");
+    assert_eq!(gdb.command("p11"), "0204");
+    // A RET with an empty stack leaves the core where it is
+    assert_eq!(gdb.command("M204,2:00ee"), "OK");
+    assert_eq!(gdb.command("s"), "S05");
+    assert_eq!(gdb.command("p11"), "0204");
+
+    assert_eq!(gdb.command("D"), "OK");
+    assert_eq!(server.join().unwrap(), Ended::Detached);
+}
+
+#[test]
+fn continue_stops_at_breakpoints() {
+    // LD V0, 1; ADD V0, 1; JP 0x202
+    let (mut gdb, server) = start(Chip8::from_rom(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]).unwrap());
+
+    assert_eq!(gdb.command("Z0,204,2"), "OK");
+    assert_eq!(gdb.command("c"), "S05");
+    assert_eq!(gdb.command("p11"), "0204");
+    assert_eq!(gdb.command("p0"), "02");
+    // Continuing from the breakpoint runs round the loop once
+    assert_eq!(gdb.command("c"), "S05");
+    assert_eq!(gdb.command("p0"), "03");
+
+    // Unsupported breakpoint kinds get the empty reply
+    assert_eq!(gdb.command("Z2,300,1"), "");
+    assert_eq!(gdb.command("z0,204,2"), "OK");
+    // Runs until interrupted by Ctrl-C instead
+    gdb.send("c200");
+    gdb.send_raw(&[0x03]);
+    assert_eq!(gdb.reply(), "S02");
+
+    // Kill has no reply
+    gdb.send("k");
+    assert_eq!(server.join().unwrap(), Ended::Killed);
+}
+
+#[test]
+fn gameboy_registers_are_little_endian() {
+    let (mut gdb, server) = start(GameBoy::from_rom(&vec![0; 0x8000]));
+
+    // A, F, B, C, D, E, H, L, SP, PC
+    assert_eq!(gdb.command("g"), "01b0001300d8014dfeff0001");
+    assert_eq!(gdb.command("P9=5001"), "OK");
+    assert_eq!(gdb.command("p9"), "5001");
+    // F's low nibble is always clear
+    assert_eq!(gdb.command("P1=ff"), "OK");
+    assert_eq!(gdb.command("p1"), "f0");
+
+    assert_eq!(gdb.command("MC000,2:1234"), "OK");
+    assert_eq!(gdb.command("mc000,2"), "1234");
+    assert_eq!(gdb.command("M0100,1:00"), "E0e");
+
+    drop(gdb);
+    assert_eq!(server.join().unwrap(), Ended::HungUp);
+}
+
+#[test]
+fn target_description_lists_registers_in_order() {
+    let xml = target_xml(&GameBoy::default());
+    let a = xml.find("<reg name=\"a\" bitsize=\"8\"").unwrap();
+    let sp = xml
+        .find("<reg name=\"sp\" bitsize=\"16\" type=\"data_ptr\"/>")
+        .unwrap();
+    let pc = xml
+        .find("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>")
+        .unwrap();
+    assert!(a < sp && sp < pc);
+
+    let (mut gdb, _server) = start(Chip8::from_rom(&[]).unwrap());
+    let first = gdb.command("qXfer:features:read:target.xml:0,20");
+    assert_eq!(
+        first,
+        format!("m{}", &target_xml(&Chip8::from_rom(&[]).unwrap())[..0x20])
+    );
+    let whole = gdb.command("qXfer:features:read:target.xml:0,1000");
+    assert!(whole.starts_with("l<?xml"));
+    assert!(whole.contains("<reg name=\"vf\" bitsize=\"8\" type=\"int\"/>"));
+}
@@ -0,0 +1,67 @@
+use emulators::chip8::Chip8;
+use emulators::emulator::EmulatorDevice;
+use emulators::headless::{HeadlessRunner, ScriptedInput, check_golden};
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/goldens");
+
+fn chip8_runner(rom: &[u16]) -> HeadlessRunner {
+    let bytes: Vec<u8> = rom.iter().flat_map(|op| op.to_be_bytes()).collect();
+
+    let mut chip8 = Chip8::from_rom(&bytes).unwrap();
+    chip8.set_instructions_per_frame(10);
+
+    HeadlessRunner::new(EmulatorDevice::new(chip8))
+}
+
+fn golden(name: &str) -> String {
+    format!("{}/{}.png", GOLDEN_DIR, name)
+}
+
+#[test]
+fn chip8_font() {
+    // Draws the 16 built-in hex digits in two rows of eight
+    let mut runner = chip8_runner(&[
+        0x6000, // LD V0, 0
+        0x6100, // LD V1, 0
+        0x6200, // LD V2, 0
+        0xF029, // LD F, V0
+        0xD125, // DRW V1, V2, 5
+        0x7001, // ADD V0, 1
+        0x7108, // ADD V1, 8
+        0x3008, // SE V0, 8
+        0x1216, // JP 0x216
+        0x6100, // LD V1, 0
+        0x620A, // LD V2, 10
+        0x3010, // SE V0, 16
+        0x1206, // JP 0x206
+        0x121A, // JP 0x21A
+    ]);
+
+    runner.run_frames(30);
+    check_golden(&runner.frame_image(), golden("chip8_font")).unwrap();
+}
+
+#[test]
+fn chip8_keypad() {
+    // Waits for a key and XORs its digit into the middle of the screen
+    let mut runner = chip8_runner(&[
+        0xF00A, // LD V0, K
+        0xF029, // LD F, V0
+        0x611C, // LD V1, 28
+        0x620D, // LD V2, 13
+        0xD125, // DRW V1, V2, 5
+        0x1200, // JP 0x200
+    ])
+    .with_inputs(vec![
+        ScriptedInput::press(2, 0x5),
+        ScriptedInput::release(4, 0x5),
+        ScriptedInput::press(10, 0x6),
+        ScriptedInput::release(12, 0x6),
+    ]);
+
+    runner.run_frames(8);
+    check_golden(&runner.frame_image(), golden("chip8_keypad_5")).unwrap();
+
+    runner.run_frames(8);
+    check_golden(&runner.frame_image(), golden("chip8_keypad_5_xor_6")).unwrap();
+}
@@ -0,0 +1,171 @@
+use emulators::chip8::Chip8;
+use emulators::config;
+use emulators::emulator::Emulator;
+use emulators::gameboy::GameBoy;
+use emulators::keymap::{HOTKEYS, Keymap, parse_key};
+use winit::keyboard::KeyCode;
+
+const BINDINGS: &str = r#"
+[chip8]
+5 = "ArrowUp"
+8 = ["ArrowDown", "S"]
+F = []
+
+[chip8.rom."PONG.ch8"]
+1 = "W"
+4 = "KeyS"
+
+[gameboy]
+start = "Space"
+"#;
+
+fn chip8_keymap() -> Keymap {
+    Keymap::defaults("chip8", Chip8::from_rom(&[]).unwrap().buttons())
+}
+
+#[test]
+fn chip8_defaults_to_the_left_of_the_keyboard() {
+    let keymap = chip8_keymap();
+    let keys = [
+        (KeyCode::Digit1, 0x1),
+        (KeyCode::Digit4, 0xC),
+        (KeyCode::KeyW, 0x5),
+        (KeyCode::KeyX, 0x0),
+        (KeyCode::KeyV, 0xF),
+    ];
+    for (code, key) in keys {
+        assert_eq!(keymap.button(code), Some(key), "{:?}", code);
+    }
+    assert_eq!(keymap.button(KeyCode::KeyP), None);
+    assert!((0..16).all(|key| keymap.keys(key).len() == 1));
+}
+
+#[test]
+fn defaults_stay_clear_of_hotkeys_and_modifiers() {
+    // Shift+F12 would press a button bound to Shift
+    let modifiers = [
+        KeyCode::ShiftLeft,
+        KeyCode::ShiftRight,
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::AltLeft,
+        KeyCode::AltRight,
+    ];
+    let keymaps = [
+        chip8_keymap(),
+        Keymap::defaults("gameboy", GameBoy::from_rom(&[]).buttons()),
+    ];
+    for keymap in keymaps {
+        for button in 0..keymap.buttons().len() {
+            for code in keymap.keys(button) {
+                assert!(!HOTKEYS.contains(code), "{:?}", code);
+                assert!(!modifiers.contains(code), "{:?}", code);
+            }
+        }
+    }
+}
+
+#[test]
+fn rom_sections_override_emulator_sections() {
+    let document = config::parse(BINDINGS).unwrap();
+
+    let mut keymap = chip8_keymap();
+    keymap.apply(&document, "chip8", Some("BRIX.ch8")).unwrap();
+    assert_eq!(keymap.keys(0x5), [KeyCode::ArrowUp]);
+    assert_eq!(keymap.keys(0x8), [KeyCode::ArrowDown, KeyCode::KeyS]);
+    assert!(keymap.keys(0xF).is_empty());
+    assert_eq!(keymap.button(KeyCode::KeyW), None);
+    assert_eq!(keymap.button(KeyCode::Digit1), Some(0x1));
+
+    let mut keymap = chip8_keymap();
+    keymap.apply(&document, "chip8", Some("PONG.ch8")).unwrap();
+    assert_eq!(keymap.keys(0x1), [KeyCode::KeyW]);
+    // S moved from 8 to 4
+    assert_eq!(keymap.keys(0x4), [KeyCode::KeyS]);
+    assert_eq!(keymap.keys(0x8), [KeyCode::ArrowDown]);
+    assert_eq!(keymap.keys(0x5), [KeyCode::ArrowUp]);
+
+    let mut keymap = Keymap::defaults("gameboy", GameBoy::from_rom(&[]).buttons());
+    keymap
+        .apply(&document, "gameboy", Some("PONG.ch8"))
+        .unwrap();
+    assert_eq!(keymap.button(KeyCode::Space), keymap.position("Start"));
+    assert_eq!(keymap.button(KeyCode::Enter), None);
+    assert_eq!(keymap.button(KeyCode::ArrowUp), keymap.position("Up"));
+}
+
+#[test]
+fn reports_unknown_buttons_and_keys() {
+    let mut keymap = chip8_keymap();
+    let document = config::parse("[chip8]\n1 = \"KeyQ\"\nG = \"KeyG\"\n").unwrap();
+    let error = format!("{:#}", keymap.apply(&document, "chip8", None).unwrap_err());
+    assert!(error.contains("line 3: unknown button G"), "{}", error);
+
+    let document = config::parse("[chip8]\n\n1 = [\"Hyper\"]\n").unwrap();
+    let error = format!("{:#}", keymap.apply(&document, "chip8", None).unwrap_err());
+    assert!(error.contains("line 3"), "{}", error);
+    assert!(error.contains("unknown key Hyper"), "{}", error);
+
+    assert_eq!(parse_key("arrowleft").unwrap(), KeyCode::ArrowLeft);
+    assert_eq!(parse_key("q").unwrap(), KeyCode::KeyQ);
+    assert_eq!(parse_key("7").unwrap(), KeyCode::Digit7);
+    assert_eq!(parse_key("F12").unwrap(), KeyCode::F12);
+}
+
+#[test]
+fn hotkeys_cannot_be_bound() {
+    let mut keymap = chip8_keymap();
+    for key in ["F2", "F12", "PageDown", "P", "Tab", "Minus", "Backspace"] {
+        let document = config::parse(&format!("[chip8]\n1 = [\"KeyQ\", \"{}\"]\n", key)).unwrap();
+        let error = format!("{:#}", keymap.apply(&document, "chip8", None).unwrap_err());
+        assert!(error.contains("line 2"), "{}", error);
+        assert!(error.contains("reserved for a hotkey"), "{}", error);
+    }
+    assert_eq!(keymap, chip8_keymap());
+
+    let document = config::parse("[chip8]\n1 = \"F1\"\n").unwrap();
+    keymap.apply(&document, "chip8", None).unwrap();
+    assert_eq!(keymap.button(KeyCode::F1), Some(0x1));
+}
+
+#[test]
+fn invalid_files_leave_the_keymap_unchanged() {
+    let path = std::env::temp_dir().join(format!("emulators-keymap-{}.toml", std::process::id()));
+    std::fs::write(&path, "[chip8]\n1 = \"KeyQ\"\n2 = 3\n").unwrap();
+
+    let mut keymap = chip8_keymap();
+    let error = format!("{:#}", keymap.load(&path, "chip8", None).unwrap_err());
+    assert_eq!(keymap, chip8_keymap());
+    assert!(error.contains("line 3"), "{}", error);
+
+    std::fs::write(&path, "[chip8]\n1 = \"KeyQ\"\n").unwrap();
+    keymap.load(&path, "chip8", None).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(keymap.button(KeyCode::KeyQ), Some(0x1));
+}
+
+#[test]
+fn gameboy_buttons_read_through_p1() {
+    let mut gameboy = GameBoy::from_rom(&[]);
+    let keymap = Keymap::defaults("gameboy", gameboy.buttons());
+    let press = |gameboy: &mut GameBoy, code, is_pressed| {
+        gameboy.set_button(keymap.button(code).unwrap(), is_pressed);
+    };
+    press(&mut gameboy, KeyCode::KeyX, true);
+    press(&mut gameboy, KeyCode::ArrowLeft, true);
+    assert_eq!(gameboy.read(0xFF0F) & 0x10, 0x10);
+
+    // Action buttons with bit 5 low: A
+    gameboy.write(0xFF00, 0x10);
+    assert_eq!(gameboy.read(0xFF00), 0xDE);
+    // Directions with bit 4 low: Left
+    gameboy.write(0xFF00, 0x20);
+    assert_eq!(gameboy.read(0xFF00), 0xED);
+    // Neither group
+    gameboy.write(0xFF00, 0xFF);
+    assert_eq!(gameboy.read(0xFF00), 0xFF);
+
+    press(&mut gameboy, KeyCode::ArrowLeft, false);
+    gameboy.write(0xFF00, 0x20);
+    assert_eq!(gameboy.read(0xFF00), 0xEF);
+}
@@ -0,0 +1,14 @@
+use emulators::logging::{STATUS_TARGET, init};
+
+// Only one logger can be installed per process, so this is the only test here
+#[test]
+fn logging_still_works_without_the_trace_file() {
+    let path = std::env::temp_dir()
+        .join(format!("emulators-missing-{}", std::process::id()))
+        .join("trace.log");
+    let error = format!("{:#}", init(false, Some(&path)).unwrap_err());
+    assert!(error.contains("Could not create"), "{}", error);
+
+    assert!(log::log_enabled!(target: STATUS_TARGET, log::Level::Info));
+    assert!(log::log_enabled!(log::Level::Error));
+}
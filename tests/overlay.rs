@@ -0,0 +1,168 @@
+use emulators::chip8::Chip8;
+use emulators::chip8::disasm::disassemble;
+use emulators::debug::{DebugSnapshot, DebugTarget, MEMORY_PAGE, disassembly_around};
+use emulators::emulator::Emulator;
+use emulators::gameboy::GameBoy;
+use emulators::overlay::{
+    LineStyle, PANEL_COLUMNS, PANEL_ROWS, has_glyph, panel_lines, render_panel, scroll_memory,
+};
+
+// A 32 KiB ROM with `code` at the entry point
+fn gameboy_with(code: &[u8]) -> GameBoy {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(code);
+    GameBoy::from_rom(&rom)
+}
+
+#[test]
+fn chip8_snapshot_has_registers_stack_and_memory() {
+    // LD V0, 5; CALL 0x206; JP 0x204; LD I, 0x300
+    let mut chip8 = Chip8::from_rom(&[0x60, 0x05, 0x22, 0x06, 0x12, 0x04, 0xA3, 0x00]).unwrap();
+    chip8.tick();
+    chip8.tick();
+
+    let snapshot = DebugSnapshot::capture(&chip8, 0x200);
+    assert_eq!(snapshot.registers[0].value, 5);
+    assert_eq!(snapshot.status, ["Stack: 204"]);
+    assert_eq!(snapshot.memory.len(), MEMORY_PAGE as usize);
+    assert_eq!(
+        snapshot.memory[..4],
+        [Some(0x60), Some(0x05), Some(0x22), Some(0x06)]
+    );
+
+    let current: Vec<_> = snapshot
+        .disassembly
+        .iter()
+        .filter(|line| line.current)
+        .collect();
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0].addr, 0x206);
+    assert_eq!(current[0].text, "LD I, 0x300");
+    // Four instructions before PC, eight after
+    assert_eq!(snapshot.disassembly.len(), 13);
+    assert_eq!(snapshot.disassembly[0].addr, 0x1FE);
+}
+
+#[test]
+fn memory_past_the_end_is_unmapped() {
+    let chip8 = Chip8::from_rom(&[]).unwrap();
+    let snapshot = DebugSnapshot::capture(&chip8, 0xFF8);
+    assert_eq!(snapshot.memory[6], Some(0));
+    assert_eq!(snapshot.memory[7], None);
+
+    let snapshot = DebugSnapshot::capture(&chip8, 0xFFC0);
+    assert_eq!(snapshot.memory.len(), MEMORY_PAGE as usize);
+    assert!(snapshot.memory.iter().all(Option::is_none));
+}
+
+#[test]
+fn gameboy_disassembly_lines_up_with_pc() {
+    // LD BC, 0x1234; LD A, 0x12; INC A; JP 0x0150 with PC on the JP
+    let mut gameboy = gameboy_with(&[0x01, 0x34, 0x12, 0x3E, 0x12, 0x3C, 0xC3, 0x50, 0x01]);
+    gameboy.set_register(9, 0x106);
+
+    let lines = disassembly_around(&gameboy, 3, 1);
+    let text: Vec<(u16, &str, bool)> = lines
+        .iter()
+        .map(|line| (line.addr, line.text.as_str(), line.current))
+        .collect();
+    assert_eq!(
+        text,
+        [
+            (0x100, "LD BC, 0x1234", false),
+            (0x103, "LD A, 0x12", false),
+            (0x105, "INC A", false),
+            (0x106, "JP 0x0150", true),
+            (0x109, "NOP", false),
+        ]
+    );
+
+    let status = gameboy.status();
+    assert_eq!(status.len(), 5);
+    assert!(status[3].starts_with("DIV="), "{}", status[3]);
+}
+
+#[test]
+fn panel_lays_out_every_section() {
+    let mut chip8 = Chip8::from_rom(&[0x60, 0x05]).unwrap();
+    chip8.tick();
+    let lines = panel_lines(&DebugSnapshot::capture(&chip8, 0), PANEL_COLUMNS);
+
+    assert_eq!(lines[0].text, "Registers");
+    assert_eq!(lines[0].style, LineStyle::Heading);
+    assert_eq!(lines[1].text, "V0=05 V1=00 V2=00 V3=00 V4=00");
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.text == "VF=00 I=0000 PC=0202 SP=00 DT=00")
+    );
+    assert!(lines.iter().any(|line| line.text == "Stack:"));
+
+    let current = lines
+        .iter()
+        .find(|line| line.style == LineStyle::Current)
+        .unwrap();
+    assert_eq!(current.text, "> 0202: SYS 0x000");
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.text == "Memory 0000 (PgUp/PgDn)")
+    );
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.text == "0000: F0 90 90 90 F0 20 60 20")
+    );
+
+    assert!(lines.len() <= PANEL_ROWS);
+    assert!(lines.iter().all(|line| line.text.len() <= PANEL_COLUMNS));
+
+    let lines = panel_lines(
+        &DebugSnapshot::capture(&gameboy_with(&[]), 0xC000),
+        PANEL_COLUMNS,
+    );
+    assert!(lines.len() <= PANEL_ROWS);
+    assert_eq!(lines[1].text, "A=01 F=B0 B=00 C=13 D=00 E=D8");
+    assert_eq!(lines[2].text, "H=01 L=4D SP=FFFE PC=0100");
+}
+
+#[test]
+fn memory_view_scrolls_by_pages() {
+    assert_eq!(scroll_memory(0x200, 1), 0x280);
+    assert_eq!(scroll_memory(0x200, -1), 0x180);
+    assert_eq!(scroll_memory(0, -1), 0u16.wrapping_sub(MEMORY_PAGE));
+}
+
+#[test]
+fn every_disassembled_character_has_a_glyph() {
+    let mut texts: Vec<String> = (0..=0xFFFF).map(disassemble).collect();
+    for opcode in 0..=0xFFu8 {
+        let rom = [opcode, 0xCB, 0xFE];
+        texts.push(emulators::gameboy::disasm::disassemble(0, |addr| rom[addr as usize]).0);
+        texts.push(emulators::gameboy::disasm::disassemble_cb(opcode));
+    }
+    texts.push("Registers Disassembly Memory (PgUp/PgDn) Stack: Waiting for a key".to_string());
+    for text in texts {
+        assert!(text.chars().all(has_glyph), "{}", text);
+    }
+}
+
+#[test]
+fn panel_is_drawn_in_the_line_colors() {
+    let lines = panel_lines(
+        &DebugSnapshot::capture(&Chip8::from_rom(&[]).unwrap(), 0),
+        PANEL_COLUMNS,
+    );
+    let image = render_panel(&lines, PANEL_COLUMNS, PANEL_ROWS);
+    assert_eq!(image.dimensions(), (32 * 6 + 8, 48 * 9 + 8));
+
+    let colors: std::collections::BTreeSet<[u8; 4]> = image.pixels().map(|pixel| pixel.0).collect();
+    // Background, headings, text and the current instruction
+    assert_eq!(colors.len(), 4);
+
+    // The heading's R starts at the margin: its top row is 11110
+    let row: Vec<bool> = (4..9)
+        .map(|x| image.get_pixel(x, 4).0 != [20, 20, 28, 255])
+        .collect();
+    assert_eq!(row, [true, true, true, true, false]);
+}
@@ -0,0 +1,69 @@
+use emulators::chip8::Chip8;
+use emulators::emulator::{Emulator, EmulatorDevice};
+use emulators::headless::HeadlessRunner;
+use emulators::palette::{Palette, builtin_palettes, load_palettes, select_palette};
+use image::Rgba;
+
+#[test]
+fn two_colors_are_blended_to_four() {
+    let palette = Palette::from_hex("test", "#000000, #FFFFFF").unwrap();
+    assert_eq!(
+        palette.colors,
+        [[0, 0, 0], [85, 85, 85], [170, 170, 170], [255, 255, 255]]
+    );
+
+    assert!(Palette::from_hex("test", "#000000,#111111,#222222").is_err());
+}
+
+#[test]
+fn selects_by_name_or_hex() {
+    let mut palettes = builtin_palettes();
+    let builtin = palettes.len();
+
+    assert_eq!(select_palette(&mut palettes, "pocket-gray").unwrap(), 1);
+    assert_eq!(palettes.len(), builtin);
+
+    let index = select_palette(&mut palettes, "#112233,#445566").unwrap();
+    assert_eq!(index, builtin);
+    assert_eq!(palettes[index].colors[3], [0x44, 0x55, 0x66]);
+
+    assert!(select_palette(&mut palettes, "not-a-palette").is_err());
+}
+
+#[test]
+fn loads_palette_file() {
+    let path = std::env::temp_dir().join(format!("emulators-palettes-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        "[[palette]]\nname = \"red\"\ncolors = [\"#FF0000\", \"#400000\"]\n\n\
+         [[palette]]\nname = \"bad\"\ncolors = \"#FF0000\"\n",
+    )
+    .unwrap();
+
+    let error = format!("{:#}", load_palettes(&path).unwrap_err());
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(error.contains("line 5"), "{}", error);
+    assert!(error.contains("has 1 colors"), "{}", error);
+}
+
+#[test]
+fn chip8_uses_background_and_darkest_shades() {
+    // Draws the top row of the 0 glyph at the top left
+    let rom: Vec<u8> = [0x6000u16, 0xF029, 0xD001, 0x1206]
+        .iter()
+        .flat_map(|op| op.to_be_bytes())
+        .collect();
+    let palette = builtin_palettes().remove(0);
+
+    let mut chip8 = Chip8::from_rom(&rom).unwrap();
+    chip8.set_palette(&palette);
+    let mut runner = HeadlessRunner::new(EmulatorDevice::new(chip8));
+    runner.run_frames(1);
+
+    let image = runner.frame_image();
+    let [r, g, b] = palette.colors[3];
+    assert_eq!(*image.get_pixel(0, 0), Rgba([r, g, b, 255]));
+    let [r, g, b] = palette.colors[0];
+    assert_eq!(*image.get_pixel(63, 31), Rgba([r, g, b, 255]));
+}
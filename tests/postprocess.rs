@@ -0,0 +1,114 @@
+use emulators::postprocess::{
+    PassScale, builtin_presets, find_presets, frame_blend_pass, load_preset, pass_size,
+    validate_pass,
+};
+
+#[test]
+fn builtin_presets_compile() {
+    for preset in builtin_presets() {
+        for pass in &preset.passes {
+            if let Err(error) = validate_pass(pass) {
+                panic!("{} / {}:\n{}", preset.name, pass.label, error);
+            }
+        }
+    }
+}
+
+#[test]
+fn frame_blend_pass_compiles() {
+    if let Err(error) = validate_pass(&frame_blend_pass()) {
+        panic!("{}", error);
+    }
+}
+
+#[test]
+fn broken_pass_reports_the_error() {
+    let mut pass = builtin_presets()
+        .into_iter()
+        .find(|preset| preset.name == "scanlines")
+        .unwrap()
+        .passes
+        .remove(0);
+    pass.source = pass.source.replace("textureSample", "textureSampel");
+
+    let error = validate_pass(&pass).unwrap_err().to_string();
+    assert!(error.contains("textureSampel"), "{}", error);
+}
+
+#[test]
+fn example_presets_load() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders");
+
+    let manifests = find_presets(&dir).unwrap();
+    assert!(!manifests.is_empty());
+    for manifest in manifests {
+        if let Err(error) = load_preset(&manifest) {
+            panic!("{:#}", error);
+        }
+    }
+}
+
+#[test]
+fn broken_user_shader_reports_its_own_line() {
+    let dir = std::env::temp_dir().join(format!("emulators-preset-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("broken.toml"),
+        "[[pass]]\nshader = \"broken.wgsl\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("broken.wgsl"),
+        "@fragment\nfn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {\n\treturn nope;\n}\n",
+    )
+    .unwrap();
+
+    let error = format!("{:#}", load_preset(&dir.join("broken.toml")).unwrap_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(error.contains("broken.wgsl:3:"), "{}", error);
+}
+
+#[test]
+fn oversized_pass_scale_is_rejected() {
+    let dir = std::env::temp_dir().join(format!("emulators-scale-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("pass.wgsl"), "").unwrap();
+    std::fs::write(
+        dir.join("huge.toml"),
+        "[[pass]]\nshader = \"pass.wgsl\"\nscale = 1e300\n",
+    )
+    .unwrap();
+
+    let error = format!("{:#}", load_preset(&dir.join("huge.toml")).unwrap_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(error.contains("line 1: scale must be"), "{}", error);
+}
+
+#[test]
+fn pass_sizes_stay_within_the_texture_limit() {
+    assert_eq!(
+        pass_size(PassScale::Source(2.0), (160, 144), (1920, 1080), 8192),
+        (320, 288)
+    );
+    assert_eq!(
+        pass_size(PassScale::Viewport(0.5), (160, 144), (1920, 1080), 8192),
+        (960, 540)
+    );
+    assert_eq!(
+        pass_size(PassScale::Viewport(16.0), (160, 144), (1920, 1080), 8192),
+        (8192, 8192)
+    );
+
+    // A source pass chained off an already clamped one
+    let size = pass_size(PassScale::Source(16.0), (64, 32), (0, 0), 8192);
+    assert_eq!(
+        pass_size(PassScale::Source(16.0), size, (0, 0), 8192),
+        (8192, 8192)
+    );
+    assert_eq!(
+        pass_size(PassScale::Source(0.001), (64, 32), (0, 0), 8192),
+        (1, 1)
+    );
+}
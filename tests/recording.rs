@@ -0,0 +1,107 @@
+use std::{fs, path::PathBuf};
+
+use emulators::chip8::Chip8;
+use emulators::emulator::{EmulatorDevice, FrameView, PixelFormat};
+use emulators::headless::HeadlessRunner;
+use emulators::recording::Recorder;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("emulators-{}-{}", std::process::id(), name))
+}
+
+// Starts a 30 tick beep, then spins
+fn beeping_chip8() -> HeadlessRunner {
+    let rom: Vec<u8> = [
+        0x601Eu16, // LD V0, 30
+        0xF018,    // LD ST, V0
+        0x1204,    // JP 0x204
+    ]
+    .iter()
+    .flat_map(|op| op.to_be_bytes())
+    .collect();
+
+    HeadlessRunner::new(EmulatorDevice::new(Chip8::from_rom(&rom).unwrap()))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn y4m_has_every_frame_and_wav_every_sample() {
+    let path = temp_path("recording.y4m");
+    let mut runner = beeping_chip8();
+    runner.start_recording(&path).unwrap();
+    runner.run_frames(60);
+    assert_eq!(runner.finish_recording().unwrap(), Some(path.clone()));
+
+    let video = fs::read(&path).unwrap();
+    let header_end = video.iter().position(|&b| b == b'\n').unwrap();
+    let header = std::str::from_utf8(&video[..header_end]).unwrap();
+    assert!(header.starts_with("YUV4MPEG2 W64 H32 F"), "{}", header);
+    assert!(header.ends_with("C444"), "{}", header);
+    let frame_bytes = b"FRAME\n".len() + 64 * 32 * 3;
+    assert_eq!(video.len() - header_end - 1, 60 * frame_bytes);
+
+    // One timer tick per frame, 16-bit mono
+    let wav_path = path.with_extension("wav");
+    let audio = fs::read(&wav_path).unwrap();
+    assert_eq!(&audio[..4], b"RIFF");
+    assert_eq!(&audio[8..12], b"WAVE");
+    assert_eq!(u32_at(&audio, 24), 44_100);
+    assert_eq!(u32_at(&audio, 40), 60 * 735 * 2);
+    assert_eq!(u32_at(&audio, 4) as usize, audio.len() - 8);
+
+    // The beep is audible at the start and silent once the sound timer ran out
+    let samples: Vec<i16> = audio[44..]
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    assert!(samples[..735].iter().any(|&sample| sample != 0));
+    assert!(samples[59 * 735..].iter().all(|&sample| sample == 0));
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(wav_path).unwrap();
+}
+
+#[test]
+fn gif_drops_frames_faster_than_its_delays() {
+    let path = temp_path("recording.gif");
+    let data = vec![255u8; 4 * 4 * 4];
+    let frame = FrameView {
+        data: &data,
+        width: 4,
+        height: 4,
+        format: PixelFormat::Rgba8,
+    };
+
+    // 60 fps frames are 1.67cs apart, so kept frames alternate between 2cs and 3cs to keep time
+    let mut recorder = Recorder::create(&path, 60.0).unwrap();
+    for _ in 0..60 {
+        recorder.push_frame(frame);
+    }
+    recorder.finish().unwrap();
+
+    let file = fs::File::open(&path).unwrap();
+    let frames =
+        image::AnimationDecoder::into_frames(image::codecs::gif::GifDecoder::new(file).unwrap())
+            .collect_frames()
+            .unwrap();
+    assert_eq!(frames.len(), 40);
+    let delays: Vec<u32> = frames
+        .iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            numer / denom
+        })
+        .collect();
+    assert!(delays.iter().all(|&delay| delay == 20 || delay == 30));
+    assert_eq!(delays.iter().sum::<u32>(), 1000);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn unknown_extensions_are_rejected() {
+    assert!(Recorder::create(&temp_path("recording.mp4"), 60.0).is_err());
+}
@@ -0,0 +1,85 @@
+use emulators::emulator::{Emulator, FrameView, MachineClock, PixelFormat};
+use emulators::scheduler::Scheduler;
+
+// Takes 3, 4 or 5 cycles per instruction and records when timer ticks land
+#[derive(Default)]
+struct CountingCore {
+    instructions: u64,
+    timer_ticks_at: Vec<u64>,
+}
+
+impl Emulator for CountingCore {
+    fn tick(&mut self) -> u32 {
+        self.instructions += 1;
+        3 + (self.instructions % 3) as u32
+    }
+
+    fn clock(&self) -> MachineClock {
+        MachineClock {
+            hz: 6000,
+            timer_period: 50,
+            frame_period: 100,
+        }
+    }
+
+    fn framebuffer(&self) -> FrameView<'_> {
+        FrameView {
+            data: &[0, 0, 0, 255],
+            width: 1,
+            height: 1,
+            format: PixelFormat::Rgba8,
+        }
+    }
+
+    fn take_frame_ready(&mut self) -> bool {
+        false
+    }
+
+    fn timer_tick(&mut self) {
+        self.timer_ticks_at.push(self.instructions);
+    }
+}
+
+fn run(frames: u64) -> (CountingCore, Scheduler) {
+    let mut core = CountingCore::default();
+    let mut scheduler = Scheduler::new(core.clock());
+    for _ in 0..frames {
+        scheduler.run_frame(&mut core);
+    }
+    (core, scheduler)
+}
+
+#[test]
+fn timer_ticks_follow_master_clock() {
+    let (core, scheduler) = run(60);
+
+    assert_eq!(scheduler.clock().frame_rate(), 60.0);
+    assert!(scheduler.cycles() >= 6000 && scheduler.cycles() < 6005);
+    assert_eq!(core.timer_ticks_at.len() as u64, scheduler.cycles() / 50);
+}
+
+#[test]
+fn runs_are_deterministic() {
+    let (first, _) = run(60);
+    let (second, _) = run(60);
+
+    assert_eq!(first.instructions, second.instructions);
+    assert_eq!(first.timer_ticks_at, second.timer_ticks_at);
+}
+
+#[test]
+fn stepping_matches_whole_frames() {
+    let (framed, framed_scheduler) = run(3);
+
+    let mut core = CountingCore::default();
+    let mut scheduler = Scheduler::new(core.clock());
+    let mut frames = 0;
+    while frames < 3 {
+        if scheduler.step(&mut core) {
+            frames += 1;
+        }
+    }
+
+    assert_eq!(scheduler.cycles(), framed_scheduler.cycles());
+    assert_eq!(core.timer_ticks_at, framed.timer_ticks_at);
+}
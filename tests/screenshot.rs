@@ -0,0 +1,34 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use emulators::frame_buffer::Frame;
+use emulators::screenshot::{frame_image, timestamp};
+
+#[test]
+fn timestamps_are_utc_and_sortable() {
+    assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000-000");
+    assert_eq!(
+        timestamp(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)),
+        "20231114-221320-123"
+    );
+    // Leap day
+    assert_eq!(
+        timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+        "20000229-000000-000"
+    );
+}
+
+#[test]
+fn frame_image_keeps_native_resolution() {
+    let frame = Frame {
+        data: vec![1, 2, 3, 255, 4, 5, 6, 255],
+        width: 2,
+        height: 1,
+        ..Default::default()
+    };
+
+    let image = frame_image(&frame).unwrap();
+    assert_eq!(image.dimensions(), (2, 1));
+    assert_eq!(image.get_pixel(1, 0).0, [4, 5, 6, 255]);
+
+    assert!(frame_image(&Frame::default()).is_err());
+}